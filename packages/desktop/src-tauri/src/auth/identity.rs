@@ -0,0 +1,174 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use super::jwt::{self, Expected, JwkSet};
+use super::oidc::Client;
+use crate::claims;
+use crate::config::ConfigState;
+
+/// How long fetched signing keys are used before they are fetched again.
+const KEYS_TTL: Duration = Duration::from_secs(3600);
+
+/// Least time between fetches caused by an unknown `kid`, so tokens naming
+/// made-up keys cannot make the shell hammer the provider.
+const MIN_REFETCH: Duration = Duration::from_secs(30);
+
+/// Signing keys of each issuer, with when they were fetched.
+#[derive(Default)]
+pub struct KeyCache(Mutex<HashMap<String, (JwkSet, Instant)>>);
+
+/// The user behind a tenant's session, taken from its verified access token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Identity {
+    /// The tenant whose session this is.
+    pub tenant_id: String,
+    pub subject: String,
+    pub username: Option<String>,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    /// Value of `keycloak.tenantClaim`, if configured and present.
+    pub tenant: Option<String>,
+    /// Realm roles and the roles of the desktop client.
+    pub roles: Vec<String>,
+    /// Unix timestamp after which the access token is no longer accepted.
+    pub expires_at: u64,
+}
+
+impl Identity {
+    fn from_claims(tenant_id: &str, payload: &Value, tenant_claim: Option<&str>, client_id: &str) -> Result<Self, String> {
+        let text = |claim: &str| claims::string_claim(payload, claim).map(String::from);
+        let client_access = payload.get("resource_access").and_then(|access| access.get(client_id));
+        let roles: BTreeSet<String> = roles(payload.get("realm_access"))
+            .chain(roles(client_access))
+            .map(String::from)
+            .collect();
+
+        Ok(Self {
+            tenant_id: tenant_id.to_string(),
+            subject: text("sub").ok_or("token has no subject")?,
+            username: text("preferred_username"),
+            name: text("name"),
+            email: text("email"),
+            tenant: tenant_claim.and_then(text),
+            roles: roles.into_iter().collect(),
+            expires_at: payload.get("exp").and_then(Value::as_u64).unwrap_or_default(),
+        })
+    }
+}
+
+/// Keycloak's `{ "roles": [...] }` shape in `realm_access` and `resource_access`.
+fn roles(access: Option<&Value>) -> impl Iterator<Item = &str> {
+    access
+        .and_then(|access| access.get("roles"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+}
+
+/// Verifies the tenant's access token against the realm's signing keys and
+/// returns who it belongs to.
+///
+/// Blocks while signing keys are fetched.
+pub fn identity(app: &AppHandle, tenant_id: &str) -> Result<Identity, String> {
+    let tokens = super::tokens(app, tenant_id).ok_or_else(|| format!("Not logged in to tenant {}", tenant_id))?;
+    let client = super::client(app)?;
+    let payload = verify(app, &client, &tokens.access_token)?;
+
+    let tenant_claim = {
+        let state = app.state::<ConfigState>();
+        let loaded = state.0.lock().map_err(|e| e.to_string())?;
+        loaded.config.keycloak.as_ref().and_then(|k| k.tenant_claim.clone())
+    };
+    Identity::from_claims(tenant_id, &payload, tenant_claim.as_deref(), &client.client_id)
+}
+
+/// Fails unless the tenant's verified user holds `keycloak.adminRole`.
+///
+/// Blocks while signing keys are fetched.
+pub fn require_admin(app: &AppHandle, tenant_id: &str) -> Result<(), String> {
+    let role = {
+        let state = app.state::<ConfigState>();
+        let loaded = state.0.lock().map_err(|e| e.to_string())?;
+        loaded.config.keycloak.as_ref().and_then(|k| k.admin_role.clone())
+    };
+    let role = role.ok_or("No admin role is configured; set keycloak.adminRole")?;
+
+    match identity(app, tenant_id)?.roles.contains(&role) {
+        true => Ok(()),
+        false => Err(format!("This needs the `{}` role", role)),
+    }
+}
+
+fn verify(app: &AppHandle, client: &Client, token: &str) -> Result<Value, String> {
+    let header = jwt::header(token)?;
+    let cache = app.state::<KeyCache>();
+    let cached = cache.0.lock().map_err(|e| e.to_string())?.get(&client.issuer).cloned();
+
+    // Keys are fetched again when they are old, or when the token names one
+    // we do not know yet because the realm rotated its keys.
+    let keys = match cached {
+        Some((keys, fetched_at))
+            if fetched_at.elapsed() < KEYS_TTL
+                && (keys.find(&header).is_some() || fetched_at.elapsed() < MIN_REFETCH) =>
+        {
+            keys
+        }
+        _ => {
+            let keys = client.keys(&client.discover()?)?;
+            cache
+                .0
+                .lock()
+                .map_err(|e| e.to_string())?
+                .insert(client.issuer.clone(), (keys.clone(), Instant::now()));
+            keys
+        }
+    };
+
+    let key = keys.find(&header).ok_or("token is signed with an unknown key")?;
+    let expected = Expected {
+        issuer: &client.issuer,
+        audience: &client.client_id,
+    };
+    jwt::verify(token, key, &expected, super::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn collects_user_tenant_and_roles() {
+        let payload = json!({
+            "sub": "f3a1",
+            "preferred_username": "alice",
+            "email": "alice@acme.example.com",
+            "exp": 2_000,
+            "smartops": { "tenant": "acme" },
+            "realm_access": { "roles": ["user", "offline_access"] },
+            "resource_access": {
+                "smartops-desktop": { "roles": ["admin", "user"] },
+                "account": { "roles": ["manage-account"] }
+            }
+        });
+
+        let identity = Identity::from_claims("acme", &payload, Some("smartops.tenant"), "smartops-desktop").unwrap();
+
+        assert_eq!(identity.subject, "f3a1");
+        assert_eq!(identity.username.as_deref(), Some("alice"));
+        assert_eq!(identity.name, None);
+        assert_eq!(identity.tenant.as_deref(), Some("acme"));
+        assert_eq!(identity.roles, ["admin", "offline_access", "user"]);
+        assert_eq!(identity.expires_at, 2_000);
+
+        let without_claim = Identity::from_claims("acme", &payload, None, "smartops-desktop").unwrap();
+        assert_eq!(without_claim.tenant, None);
+        assert!(Identity::from_claims("acme", &json!({ "exp": 1 }), None, "smartops-desktop").is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use log::{error, info, warn};
+use serde::Deserialize;
+use tauri::{AppHandle, Manager, Url};
+
+use super::oidc::TokenSet;
+use super::refresh;
+use crate::config::ConfigState;
+use crate::{pages, windows, SecureStore};
+
+/// How much of a tenant's data `logout` removes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LogoutScope {
+    /// End the Keycloak session and forget the tokens.
+    Session,
+    /// Also wipe the tenant's cookies, local storage, cache and secure-store entries.
+    #[default]
+    Full,
+}
+
+/// Signs a tenant out and sends its window back to the start of the web app,
+/// which then asks the user to log in.
+///
+/// Local data is removed even if Keycloak cannot be reached; the session then
+/// ends on its own once its tokens expire.
+pub async fn logout(app: &AppHandle, tenant_id: &str, scope: LogoutScope) -> Result<(), String> {
+    refresh::cancel(app, tenant_id);
+    let tokens = super::tokens(app, tenant_id);
+    super::clear_tokens(app, tenant_id);
+
+    if let Some(tokens) = tokens {
+        let thread_app = app.clone();
+        let result = tauri::async_runtime::spawn_blocking(move || end_session(&thread_app, &tokens))
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Err(e) = result {
+            warn!("Could not end the Keycloak session of tenant {}: {}", tenant_id, e);
+        }
+    }
+
+    if scope == LogoutScope::Full {
+        windows::wipe_data(app, tenant_id).await?;
+        app.state::<SecureStore>()
+            .remove_prefix(&SecureStore::tenant_prefix(tenant_id))?;
+    }
+
+    info!("Logged out of tenant {}", tenant_id);
+    reload(app, tenant_id)
+}
+
+/// Fully signs out of every configured tenant, as the tray's "Sign out of all tenants" does.
+pub fn logout_all(app: &AppHandle) {
+    let tenant_ids: Vec<String> = {
+        let state = app.state::<ConfigState>();
+        let Ok(loaded) = state.0.lock() else { return };
+        loaded.config.tenants.keys().cloned().collect()
+    };
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        for tenant_id in tenant_ids {
+            if let Err(e) = logout(&app, &tenant_id, LogoutScope::Full).await {
+                error!("Failed to log out of tenant {}: {}", tenant_id, e);
+            }
+        }
+    });
+}
+
+fn end_session(app: &AppHandle, tokens: &TokenSet) -> Result<(), String> {
+    let client = super::client(app)?;
+    let provider = client.discover()?;
+    client.end_session(&provider, tokens)
+}
+
+/// Loads the tenant's `appUrl` in its open window.
+///
+/// A window on the offline page is left alone; it loads the tenant once it is reachable.
+fn reload(app: &AppHandle, tenant_id: &str) -> Result<(), String> {
+    let Some(window) = app.get_webview_window(&windows::label(tenant_id)) else { return Ok(()) };
+    if window.url().is_ok_and(|url| pages::is_page(&url)) {
+        return Ok(());
+    }
+
+    let app_url = {
+        let state = app.state::<ConfigState>();
+        let loaded = state.0.lock().map_err(|e| e.to_string())?;
+        loaded.config.tenant(tenant_id)?.app_url.clone()
+    };
+    let url = Url::parse(&app_url).map_err(|e| e.to_string())?;
+    window.navigate(url).map_err(|e| e.to_string())
+}
@@ -0,0 +1,191 @@
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Path the identity provider redirects the browser to.
+const CALLBACK_PATH: &str = "/callback";
+
+/// How often the listener checks for the browser while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+const SUCCESS_PAGE: &str = "<!doctype html><meta charset=\"utf-8\"><title>SmartOps</title>\
+    <body style=\"font-family:system-ui,sans-serif;padding:48px\">\
+    <h1>Signed in</h1><p>You can close this tab and return to SmartOps Desktop.</p></body>";
+
+const FAILURE_PAGE: &str = "<!doctype html><meta charset=\"utf-8\"><title>SmartOps</title>\
+    <body style=\"font-family:system-ui,sans-serif;padding:48px\">\
+    <h1>Sign-in failed</h1><p>Return to SmartOps Desktop and try again.</p></body>";
+
+/// A one-shot HTTP listener on `127.0.0.1` that receives the authorization response
+/// (RFC 8252 section 7.3).
+pub struct Loopback {
+    listener: TcpListener,
+    redirect_uri: String,
+}
+
+impl Loopback {
+    /// Listens on a free port chosen by the OS.
+    pub fn bind() -> Result<Self, String> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .map_err(|e| format!("cannot listen for the login redirect: {}", e))?;
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        Ok(Self {
+            listener,
+            redirect_uri: format!("http://127.0.0.1:{}{}", port, CALLBACK_PATH),
+        })
+    }
+
+    pub fn redirect_uri(&self) -> &str {
+        &self.redirect_uri
+    }
+
+    /// Waits for the browser to come back and returns the authorization code.
+    ///
+    /// Requests for other paths (such as `/favicon.ico`) are answered with 404
+    /// and ignored. Callbacks with the wrong `state` are answered with 400 and
+    /// ignored too, so another local process or page cannot cancel the login;
+    /// only the right `state` with an `error`, or the timeout, ends the wait.
+    pub fn wait_for_code(&self, expected_state: &str, timeout: Duration) -> Result<String, String> {
+        self.listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Some(result) = handle(stream, expected_state) {
+                        return result;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if Instant::now() >= deadline {
+                        return Err("timed out waiting for the browser to complete the login".to_string());
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Err(e) => return Err(format!("login redirect listener failed: {}", e)),
+            }
+        }
+    }
+}
+
+/// Answers one browser request; `None` means it was not the callback for this login.
+fn handle(mut stream: TcpStream, expected_state: &str) -> Option<Result<String, String>> {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line).ok()?;
+    let target = request_line.split_whitespace().nth(1)?;
+    let url = Url::parse(&format!("http://127.0.0.1{}", target)).ok()?;
+
+    if url.path() != CALLBACK_PATH {
+        respond(&mut stream, "404 Not Found", "");
+        return None;
+    }
+
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+
+    if param("state").as_deref() != Some(expected_state) {
+        respond(&mut stream, "400 Bad Request", FAILURE_PAGE);
+        return None;
+    }
+
+    let result = if let Some(error) = param("error") {
+        Err(match param("error_description") {
+            Some(description) => format!("{}: {}", error, description),
+            None => error,
+        })
+    } else {
+        param("code").ok_or_else(|| "the login response has no authorization code".to_string())
+    };
+
+    let (status, page) = if result.is_ok() {
+        ("200 OK", SUCCESS_PAGE)
+    } else {
+        ("400 Bad Request", FAILURE_PAGE)
+    };
+    respond(&mut stream, status, page);
+    Some(result)
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// Sends `path` to the listener like a browser; the thread returns the status line.
+    ///
+    /// Connects before returning, so requests are accepted in the order they were made.
+    fn visit(loopback: &Loopback, path: &str) -> thread::JoinHandle<String> {
+        let address = loopback.listener.local_addr().unwrap();
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, address).unwrap();
+        thread::spawn(move || {
+            let mut response = String::new();
+            let _ = stream.read_to_string(&mut response);
+            response.lines().next().unwrap_or_default().to_string()
+        })
+    }
+
+    #[test]
+    fn returns_code_and_ignores_other_paths() {
+        let loopback = Loopback::bind().unwrap();
+        assert!(loopback.redirect_uri().starts_with("http://127.0.0.1:"));
+
+        let favicon = visit(&loopback, "/favicon.ico");
+        let callback = visit(&loopback, "/callback?code=abc%2B1&state=xyz");
+
+        assert_eq!(loopback.wait_for_code("xyz", TIMEOUT), Ok("abc+1".to_string()));
+        assert!(favicon.join().unwrap().contains("404"));
+        assert!(callback.join().unwrap().contains("200"));
+    }
+
+    #[test]
+    fn rejects_foreign_state_and_provider_errors() {
+        let loopback = Loopback::bind().unwrap();
+        let foreign = visit(&loopback, "/callback?code=abc&state=other");
+        let foreign_error = visit(&loopback, "/callback?error=access_denied&state=other");
+        let browser = visit(&loopback, "/callback?code=def&state=xyz");
+        assert_eq!(loopback.wait_for_code("xyz", TIMEOUT), Ok("def".to_string()));
+        assert!(foreign.join().unwrap().contains("400"));
+        assert!(foreign_error.join().unwrap().contains("400"));
+        assert!(browser.join().unwrap().contains("200"));
+
+        let loopback = Loopback::bind().unwrap();
+        let foreign = visit(&loopback, "/callback?code=abc&state=other");
+        assert!(loopback.wait_for_code("xyz", Duration::from_millis(300)).is_err());
+        assert!(foreign.join().unwrap().contains("400"));
+
+        let loopback = Loopback::bind().unwrap();
+        visit(&loopback, "/callback?error=access_denied&error_description=User+cancelled&state=xyz");
+        assert_eq!(
+            loopback.wait_for_code("xyz", TIMEOUT),
+            Err("access_denied: User cancelled".to_string())
+        );
+    }
+
+    #[test]
+    fn gives_up_after_timeout() {
+        let loopback = Loopback::bind().unwrap();
+
+        assert!(loopback.wait_for_code("xyz", Duration::from_millis(200)).is_err());
+    }
+}
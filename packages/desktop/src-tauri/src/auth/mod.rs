@@ -0,0 +1,292 @@
+mod identity;
+mod jwt;
+mod logout;
+mod loopback;
+mod oidc;
+mod pkce;
+mod refresh;
+
+pub use identity::{identity, require_admin, Identity, KeyCache};
+pub use logout::{logout_all, LogoutScope};
+pub use oidc::TokenSet;
+pub use refresh::RefreshTasks;
+
+use log::{error, info, warn};
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::config::ConfigState;
+use crate::{claims, secure_store, windows, SecureStore};
+use loopback::Loopback;
+use oidc::{AuthorizationRequest, Client};
+use pkce::Pkce;
+
+/// How long the user has to finish logging in in the browser.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Timeout of each request to the identity provider.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a tenant window learns about its session, sent with `session-changed`
+/// and `auth-token-refreshed`.
+///
+/// The refresh token stays in the shell.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub tenant_id: String,
+    pub access_token: String,
+    pub token_type: String,
+    pub id_token: Option<String>,
+    /// Unix timestamp after which the access token is no longer accepted.
+    pub expires_at: u64,
+}
+
+impl Session {
+    fn new(tenant_id: &str, tokens: &TokenSet) -> Self {
+        Self {
+            tenant_id: tenant_id.to_string(),
+            access_token: tokens.access_token.clone(),
+            token_type: tokens.token_type.clone(),
+            id_token: tokens.id_token.clone(),
+            expires_at: tokens.expires_at,
+        }
+    }
+}
+
+/// The native client from `keycloak.issuer` and `keycloak.clientId`.
+fn client(app: &AppHandle) -> Result<Client, String> {
+    let state = app.state::<ConfigState>();
+    let loaded = state.0.lock().map_err(|e| e.to_string())?;
+    let keycloak = loaded.config.keycloak.as_ref();
+
+    match keycloak.and_then(|k| Some((k.issuer.clone()?, k.client_id.clone()?, k.scopes.clone()))) {
+        Some((issuer, client_id, scopes)) => Ok(Client {
+            issuer,
+            client_id,
+            scopes,
+            timeout: REQUEST_TIMEOUT,
+        }),
+        None => Err("Browser login is not configured; set keycloak.issuer and keycloak.clientId".to_string()),
+    }
+}
+
+fn store_key(tenant_id: &str) -> String {
+    format!("{}auth", SecureStore::tenant_prefix(tenant_id))
+}
+
+/// The tokens stored for a tenant, if it has logged in.
+pub fn tokens(app: &AppHandle, tenant_id: &str) -> Option<TokenSet> {
+    let json = app.state::<SecureStore>().get(&store_key(tenant_id))?;
+    match serde_json::from_str(&json) {
+        Ok(tokens) => Some(tokens),
+        Err(e) => {
+            warn!("Ignoring unreadable tokens of tenant {}: {}", tenant_id, e);
+            None
+        }
+    }
+}
+
+fn store_tokens(app: &AppHandle, tenant_id: &str, tokens: &TokenSet) {
+    let result = serde_json::to_string(tokens)
+        .map_err(|e| e.to_string())
+        .and_then(|json| app.state::<SecureStore>().set(&store_key(tenant_id), json));
+    if let Err(e) = result {
+        error!("Failed to store tokens of tenant {}: {}", tenant_id, e);
+    }
+}
+
+fn clear_tokens(app: &AppHandle, tenant_id: &str) {
+    if let Err(e) = app.state::<SecureStore>().remove(&store_key(tenant_id)) {
+        error!("Failed to clear tokens of tenant {}: {}", tenant_id, e);
+    }
+}
+
+/// Sends an auth event to the tenant's window.
+fn emit<S: Serialize + Clone>(app: &AppHandle, tenant_id: &str, event: &str, payload: S) {
+    if let Err(e) = app.emit_to(windows::label(tenant_id), event, payload) {
+        error!("Failed to emit {}: {}", event, e);
+    }
+}
+
+/// Sends the tenant's session to its window, unless the tenant's secure store
+/// is locked; the web app then calls `get_session` again once it is unlocked.
+fn emit_session(app: &AppHandle, tenant_id: &str, event: &str, session: Session) {
+    if secure_store::tenant_locked(app, tenant_id) {
+        info!("Not sending {} to tenant {} while its secure store is locked", event, tenant_id);
+        return;
+    }
+    emit(app, tenant_id, event, session);
+}
+
+fn tenant_name(app: &AppHandle, tenant_id: &str) -> String {
+    let state = app.state::<ConfigState>();
+    let name = state
+        .0
+        .lock()
+        .ok()
+        .and_then(|loaded| loaded.config.tenants.get(tenant_id)?.name.clone());
+    name.unwrap_or_else(|| tenant_id.to_string())
+}
+
+/// Runs an authorization-code + PKCE login for `tenant_id` in the system browser.
+///
+/// Blocks until the browser is redirected to the loopback listener, the user
+/// gives up (`LOGIN_TIMEOUT`) or the provider reports an error.
+fn login_blocking(app: &AppHandle, tenant_id: &str, force_login: bool) -> Result<TokenSet, String> {
+    let client = client(app)?;
+    let provider = client.discover()?;
+
+    let pkce = Pkce::new()?;
+    let state = pkce::random_token()?;
+    let nonce = pkce::random_token()?;
+    let loopback = Loopback::bind()?;
+    let url = client.authorization_url(
+        &provider,
+        &AuthorizationRequest {
+            redirect_uri: loopback.redirect_uri(),
+            state: &state,
+            nonce: &nonce,
+            code_challenge: &pkce.challenge,
+            force_login,
+        },
+    )?;
+
+    info!("Opening the system browser to log in to tenant {}", tenant_id);
+    app.opener()
+        .open_url(url.as_str(), None::<&str>)
+        .map_err(|e| format!("cannot open the browser: {}", e))?;
+
+    let code = loopback.wait_for_code(&state, LOGIN_TIMEOUT)?;
+    let tokens = client.exchange_code(&provider, &code, loopback.redirect_uri(), &pkce.verifier, now())?;
+
+    // Ties the ID token to this login attempt (OIDC Core 3.1.3.7).
+    if let Some(id_token) = &tokens.id_token {
+        let payload = claims::decode_payload(id_token)?;
+        if claims::string_claim(&payload, "nonce") != Some(nonce.as_str()) {
+            return Err("the ID token does not belong to this login attempt".to_string());
+        }
+    }
+
+    info!("Logged in to tenant {}", tenant_id);
+    Ok(tokens)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Logs the calling window's tenant in through the system browser.
+///
+/// On success the tokens are stored in the secure store, kept fresh in the
+/// background and the window receives `session-changed`.
+///
+/// While the tenant's secure store is locked, a user who was logged in must
+/// enter their credentials again, as for `unlock_secure_store`, and the store
+/// is unlocked; the session is never handed out otherwise.
+#[tauri::command]
+pub async fn login(window: WebviewWindow, app_handle: AppHandle) -> Result<Session, String> {
+    let tenant_id = windows::tenant_id(&app_handle, window.label()).ok_or("This window is not a tenant window")?;
+    let locked = secure_store::tenant_locked(&app_handle, &tenant_id);
+    let session = match locked && tokens(&app_handle, &tenant_id).is_some() {
+        true => reauthenticate(&app_handle, &tenant_id).await?,
+        false => sign_in(&app_handle, &tenant_id, None).await?,
+    };
+    if locked {
+        secure_store::unlock_tenant(&app_handle, &tenant_id)?;
+    }
+    let _ = window.set_focus();
+    Ok(session)
+}
+
+/// Logs the tenant in again, asking for credentials even if the browser still
+/// has a Keycloak session, and fails unless the same user signs in.
+pub async fn reauthenticate(app: &AppHandle, tenant_id: &str) -> Result<Session, String> {
+    let subject = tokens(app, tenant_id)
+        .and_then(|tokens| subject(&tokens.access_token))
+        .ok_or_else(|| format!("Not logged in to tenant {}", tenant_id))?;
+    sign_in(app, tenant_id, Some(subject)).await
+}
+
+/// Runs a browser login and takes over its tokens. With `expected_subject` the user
+/// must log in again, and as that user.
+async fn sign_in(app_handle: &AppHandle, tenant_id: &str, expected_subject: Option<String>) -> Result<Session, String> {
+    let app = app_handle.clone();
+    let id = tenant_id.to_string();
+    let force_login = expected_subject.is_some();
+    let tokens = tauri::async_runtime::spawn_blocking(move || login_blocking(&app, &id, force_login))
+        .await
+        .map_err(|e| e.to_string())?
+        .inspect_err(|e| warn!("Login to tenant {} failed: {}", tenant_id, e))?;
+
+    if expected_subject.is_some() && subject(&tokens.access_token) != expected_subject {
+        warn!("A different user logged in to tenant {} to re-authenticate", tenant_id);
+        return Err("A different user logged in".to_string());
+    }
+
+    store_tokens(app_handle, tenant_id, &tokens);
+    refresh::schedule(app_handle, tenant_id);
+
+    let session = Session::new(tenant_id, &tokens);
+    emit_session(app_handle, tenant_id, "session-changed", session.clone());
+    Ok(session)
+}
+
+/// The `sub` of a token the shell received from the token endpoint itself.
+fn subject(token: &str) -> Option<String> {
+    let payload = claims::decode_payload(token).ok()?;
+    claims::string_claim(&payload, "sub").map(String::from)
+}
+
+/// Signs a tenant out: ends its Keycloak session and forgets its tokens, and
+/// unless `scope` is `session` also wipes its webview data and secure-store entries.
+///
+/// A window may sign out its own tenant; other tenants only if its user holds
+/// `keycloak.adminRole`.
+#[tauri::command]
+pub async fn logout(
+    tenant_id: String,
+    scope: Option<LogoutScope>,
+    window: WebviewWindow,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let caller = windows::tenant_id(&app_handle, window.label()).ok_or("This window is not a tenant window")?;
+    if caller != tenant_id {
+        let app = app_handle.clone();
+        tauri::async_runtime::spawn_blocking(move || require_admin(&app, &caller))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Cannot sign out of another tenant: {}", e))?;
+    }
+    logout::logout(&app_handle, &tenant_id, scope.unwrap_or_default()).await
+}
+
+/// The calling window's user, verified against the realm's signing keys.
+#[tauri::command]
+pub async fn get_identity(window: WebviewWindow, app_handle: AppHandle) -> Result<Identity, String> {
+    let tenant_id = windows::tenant_id(&app_handle, window.label()).ok_or("This window is not a tenant window")?;
+
+    let app = app_handle.clone();
+    tauri::async_runtime::spawn_blocking(move || identity(&app, &tenant_id))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// The calling window's session, unless it has none or it has expired.
+///
+/// The access token is a secret like any other, so this fails while the
+/// tenant's secure store is locked.
+#[tauri::command]
+pub fn get_session(window: WebviewWindow, app_handle: AppHandle) -> Result<Option<Session>, String> {
+    let Some(tenant_id) = windows::tenant_id(&app_handle, window.label()) else { return Ok(None) };
+    if !secure_store::touch_tenant(&app_handle, &tenant_id) {
+        return Err(secure_store::LOCKED.to_string());
+    }
+    let Some(tokens) = tokens(&app_handle, &tenant_id) else { return Ok(None) };
+    Ok((tokens.expires_at > now()).then(|| Session::new(&tenant_id, &tokens)))
+}
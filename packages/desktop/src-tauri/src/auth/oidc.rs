@@ -0,0 +1,529 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+use url::Url;
+
+use super::jwt::JwkSet;
+
+/// The parts of an OpenID Provider's discovery document the shell uses.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Provider {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    #[serde(default)]
+    pub end_session_endpoint: Option<String>,
+}
+
+/// A native (public) client registered with the provider.
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub issuer: String,
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    pub timeout: Duration,
+}
+
+/// Parameters of one authorization request.
+#[derive(Debug, Clone)]
+pub struct AuthorizationRequest<'a> {
+    pub redirect_uri: &'a str,
+    pub state: &'a str,
+    pub nonce: &'a str,
+    pub code_challenge: &'a str,
+    /// Makes the provider ask for credentials even if its browser session is still alive.
+    pub force_login: bool,
+}
+
+/// Token endpoint response (RFC 6749 section 5.1, plus Keycloak's `refresh_expires_in`).
+#[derive(Debug, Clone, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    token_type: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    refresh_expires_in: Option<u64>,
+    #[serde(default)]
+    id_token: Option<String>,
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+/// Tokens kept for a tenant, with absolute expiry times.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenSet {
+    pub access_token: String,
+    pub token_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Unix timestamp at which the tokens were issued; 0 for tokens stored before it was recorded.
+    #[serde(default)]
+    pub issued_at: u64,
+    /// Unix timestamp after which the access token is no longer accepted.
+    pub expires_at: u64,
+    /// Unix timestamp after which the refresh token is no longer accepted, if the provider says.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_expires_at: Option<u64>,
+}
+
+/// Why a token request failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenError {
+    /// The provider refused the grant, e.g. `invalid_grant` once the SSO session has ended.
+    Rejected(String),
+    /// The provider could not be reached or answered unexpectedly; worth retrying.
+    Unavailable(String),
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::Rejected(message) | TokenError::Unavailable(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<TokenError> for String {
+    fn from(error: TokenError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Access tokens without `expires_in` are assumed to last this long.
+const DEFAULT_EXPIRES_IN: u64 = 300;
+
+impl TokenResponse {
+    fn into_token_set(self, now: u64) -> TokenSet {
+        TokenSet {
+            access_token: self.access_token,
+            token_type: self.token_type.unwrap_or_else(|| "Bearer".to_string()),
+            refresh_token: self.refresh_token,
+            id_token: self.id_token,
+            scope: self.scope,
+            issued_at: now,
+            expires_at: now + self.expires_in.unwrap_or(DEFAULT_EXPIRES_IN),
+            // Keycloak sends 0 for offline tokens, which do not expire on their own.
+            refresh_expires_at: self.refresh_expires_in.filter(|secs| *secs > 0).map(|secs| now + secs),
+        }
+    }
+}
+
+impl Client {
+    fn agent(&self) -> ureq::Agent {
+        ureq::AgentBuilder::new().timeout(self.timeout).build()
+    }
+
+    /// Fetches `<issuer>/.well-known/openid-configuration` and checks it names the same issuer.
+    pub fn discover(&self) -> Result<Provider, String> {
+        let url = format!("{}/.well-known/openid-configuration", self.issuer.trim_end_matches('/'));
+        let body = self
+            .agent()
+            .get(&url)
+            .call()
+            .map_err(|e| format!("cannot load {}: {}", url, e))?
+            .into_string()
+            .map_err(|e| format!("cannot load {}: {}", url, e))?;
+        let provider: Provider = serde_json::from_str(&body)
+            .map_err(|e| format!("invalid discovery document at {}: {}", url, e))?;
+
+        if provider.issuer.trim_end_matches('/') != self.issuer.trim_end_matches('/') {
+            return Err(format!(
+                "discovery document names issuer {} instead of {}",
+                provider.issuer, self.issuer
+            ));
+        }
+        Ok(provider)
+    }
+
+    /// Fetches the provider's current signing keys.
+    pub fn keys(&self, provider: &Provider) -> Result<JwkSet, String> {
+        let url = &provider.jwks_uri;
+        let body = self
+            .agent()
+            .get(url)
+            .call()
+            .map_err(|e| format!("cannot load {}: {}", url, e))?
+            .into_string()
+            .map_err(|e| format!("cannot load {}: {}", url, e))?;
+        serde_json::from_str(&body).map_err(|e| format!("invalid signing keys at {}: {}", url, e))
+    }
+
+    /// The URL to open in the browser to start an authorization-code + PKCE login.
+    pub fn authorization_url(&self, provider: &Provider, request: &AuthorizationRequest) -> Result<Url, String> {
+        let mut url = Url::parse(&provider.authorization_endpoint)
+            .map_err(|e| format!("invalid authorization endpoint: {}", e))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", request.redirect_uri)
+            .append_pair("scope", &self.scopes.join(" "))
+            .append_pair("state", request.state)
+            .append_pair("nonce", request.nonce)
+            .append_pair("code_challenge", request.code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        if request.force_login {
+            url.query_pairs_mut().append_pair("prompt", "login");
+        }
+        Ok(url)
+    }
+
+    /// Redeems an authorization code at the token endpoint.
+    pub fn exchange_code(
+        &self,
+        provider: &Provider,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: &str,
+        now: u64,
+    ) -> Result<TokenSet, TokenError> {
+        self.token_request(
+            provider,
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", redirect_uri),
+                ("client_id", &self.client_id),
+                ("code_verifier", code_verifier),
+            ],
+            now,
+        )
+    }
+
+    /// Renews `tokens` with their refresh token.
+    ///
+    /// Providers that do not rotate refresh tokens omit them from the response,
+    /// in which case the current one is kept.
+    pub fn refresh(&self, provider: &Provider, tokens: &TokenSet, now: u64) -> Result<TokenSet, TokenError> {
+        let refresh_token = tokens
+            .refresh_token
+            .as_deref()
+            .ok_or_else(|| TokenError::Rejected("no refresh token".to_string()))?;
+
+        let mut fresh = self.token_request(
+            provider,
+            &[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+                ("client_id", &self.client_id),
+            ],
+            now,
+        )?;
+        if fresh.refresh_token.is_none() {
+            fresh.refresh_token = tokens.refresh_token.clone();
+            fresh.refresh_expires_at = tokens.refresh_expires_at;
+        }
+        if fresh.id_token.is_none() {
+            fresh.id_token = tokens.id_token.clone();
+        }
+        Ok(fresh)
+    }
+
+    /// Ends the user's session at the provider, which also revokes its refresh tokens.
+    ///
+    /// Uses Keycloak's back-channel logout: a POST of the refresh token to the
+    /// end-session endpoint, so no browser is involved.
+    pub fn end_session(&self, provider: &Provider, tokens: &TokenSet) -> Result<(), String> {
+        let endpoint = provider
+            .end_session_endpoint
+            .as_deref()
+            .ok_or("the provider has no end-session endpoint")?;
+
+        let mut form = vec![("client_id", self.client_id.as_str())];
+        if let Some(refresh_token) = &tokens.refresh_token {
+            form.push(("refresh_token", refresh_token));
+        }
+        if let Some(id_token) = &tokens.id_token {
+            form.push(("id_token_hint", id_token));
+        }
+        self.agent()
+            .post(endpoint)
+            .send_form(&form)
+            .map_err(|e| format!("logout request failed: {}", e))?;
+        Ok(())
+    }
+
+    fn token_request(&self, provider: &Provider, form: &[(&str, &str)], now: u64) -> Result<TokenSet, TokenError> {
+        let response = match self.agent().post(&provider.token_endpoint).send_form(form) {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, response)) => return Err(token_error(status, response)),
+            Err(e) => return Err(TokenError::Unavailable(format!("token request failed: {}", e))),
+        };
+
+        let body = response
+            .into_string()
+            .map_err(|e| TokenError::Unavailable(format!("token request failed: {}", e)))?;
+        let tokens: TokenResponse = serde_json::from_str(&body)
+            .map_err(|e| TokenError::Unavailable(format!("invalid token response: {}", e)))?;
+        Ok(tokens.into_token_set(now))
+    }
+}
+
+/// Describes an OAuth error response such as `invalid_grant: Code not valid`.
+///
+/// Client errors with an OAuth error body are final; anything else may pass.
+fn token_error(status: u16, response: ureq::Response) -> TokenError {
+    #[derive(Deserialize)]
+    struct ErrorResponse {
+        error: String,
+        #[serde(default)]
+        error_description: Option<String>,
+    }
+
+    let body = response.into_string().unwrap_or_default();
+    match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(response) if (400..500).contains(&status) => TokenError::Rejected(match response.error_description {
+            Some(description) => format!("{}: {}", response.error, description),
+            None => response.error,
+        }),
+        _ => TokenError::Unavailable(format!("token endpoint answered {}", status)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    /// A minimal OpenID Provider on `127.0.0.1` serving `requests` requests.
+    ///
+    /// Returns its issuer and a channel with every `METHOD /path` line and body received.
+    fn mock_provider(token_status: &'static str, token_body: &'static str, requests: usize) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let issuer = format!("http://{}/realms/smartops", listener.local_addr().unwrap());
+        let (seen, received) = mpsc::channel();
+
+        let base = issuer.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let target = request_line.split_whitespace().take(2).collect::<Vec<_>>().join(" ");
+                let _ = seen.send(format!("{} {}", target, String::from_utf8_lossy(&body)).trim().to_string());
+
+                let (status, content) = if target.ends_with("/.well-known/openid-configuration") {
+                    (
+                        "200 OK",
+                        serde_json::json!({
+                            "issuer": base,
+                            "authorization_endpoint": format!("{}/protocol/openid-connect/auth", base),
+                            "token_endpoint": format!("{}/protocol/openid-connect/token", base),
+                            "jwks_uri": format!("{}/protocol/openid-connect/certs", base),
+                            "end_session_endpoint": format!("{}/protocol/openid-connect/logout", base),
+                        })
+                        .to_string(),
+                    )
+                } else {
+                    (token_status, token_body.to_string())
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    content.len(),
+                    content
+                )
+                .unwrap();
+            }
+        });
+
+        (issuer, received)
+    }
+
+    fn client(issuer: &str) -> Client {
+        Client {
+            issuer: issuer.to_string(),
+            client_id: "smartops-desktop".to_string(),
+            scopes: vec!["openid".to_string(), "profile".to_string()],
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    #[test]
+    fn builds_pkce_authorization_url() {
+        let (issuer, _) = mock_provider("200 OK", "{}", 1);
+        let client = client(&issuer);
+        let provider = client.discover().unwrap();
+
+        let url = client
+            .authorization_url(
+                &provider,
+                &AuthorizationRequest {
+                    redirect_uri: "http://127.0.0.1:5000/callback",
+                    state: "st",
+                    nonce: "no",
+                    code_challenge: "ch",
+                    force_login: false,
+                },
+            )
+            .unwrap();
+
+        assert!(url.as_str().starts_with(&format!("{}/protocol/openid-connect/auth?", issuer)));
+        let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        assert!(query.contains(&("scope".to_string(), "openid profile".to_string())));
+        assert!(query.contains(&("code_challenge_method".to_string(), "S256".to_string())));
+        assert!(query.contains(&("redirect_uri".to_string(), "http://127.0.0.1:5000/callback".to_string())));
+        assert!(!query.iter().any(|(name, _)| name == "prompt"));
+    }
+
+    #[test]
+    fn exchanges_code_for_tokens() {
+        let (issuer, received) = mock_provider(
+            "200 OK",
+            r#"{"access_token":"at","expires_in":300,"refresh_token":"rt","refresh_expires_in":1800,"id_token":"it","token_type":"Bearer"}"#,
+            2,
+        );
+        let client = client(&issuer);
+        let provider = client.discover().unwrap();
+
+        let tokens = client
+            .exchange_code(&provider, "code-1", "http://127.0.0.1:5000/callback", "verifier-1", 1_000)
+            .unwrap();
+
+        assert_eq!(tokens.access_token, "at");
+        assert_eq!(tokens.refresh_token.as_deref(), Some("rt"));
+        assert_eq!(tokens.issued_at, 1_000);
+        assert_eq!(tokens.expires_at, 1_300);
+        assert_eq!(tokens.refresh_expires_at, Some(2_800));
+
+        let token_request = received.iter().nth(1).unwrap();
+        assert!(token_request.starts_with("POST /realms/smartops/protocol/openid-connect/token"));
+        assert!(token_request.contains("grant_type=authorization_code"));
+        assert!(token_request.contains("code_verifier=verifier-1"));
+    }
+
+    #[test]
+    fn reports_oauth_errors() {
+        let (issuer, _) = mock_provider(
+            "400 Bad Request",
+            r#"{"error":"invalid_grant","error_description":"Code not valid"}"#,
+            2,
+        );
+        let client = client(&issuer);
+        let provider = client.discover().unwrap();
+
+        let error = client
+            .exchange_code(&provider, "stale", "http://127.0.0.1:5000/callback", "v", 0)
+            .unwrap_err();
+
+        assert_eq!(error, TokenError::Rejected("invalid_grant: Code not valid".to_string()));
+    }
+
+    #[test]
+    fn ends_the_session_with_the_refresh_token() {
+        let (issuer, received) = mock_provider("204 No Content", "", 2);
+        let client = client(&issuer);
+        let provider = client.discover().unwrap();
+        let tokens = TokenSet {
+            access_token: "at".to_string(),
+            token_type: "Bearer".to_string(),
+            refresh_token: Some("rt".to_string()),
+            id_token: None,
+            scope: None,
+            issued_at: 700,
+            expires_at: 1_000,
+            refresh_expires_at: None,
+        };
+
+        client.end_session(&provider, &tokens).unwrap();
+
+        let logout = received.iter().nth(1).unwrap();
+        assert!(logout.starts_with("POST /realms/smartops/protocol/openid-connect/logout"));
+        assert!(logout.contains("client_id=smartops-desktop"));
+        assert!(logout.contains("refresh_token=rt"));
+    }
+
+    #[test]
+    fn fetches_signing_keys() {
+        let (issuer, received) = mock_provider(
+            "200 OK",
+            r#"{"keys":[{"kid":"k1","kty":"RSA","alg":"RS256","use":"sig","n":"AQAB","e":"AQAB"},{"kid":"k2","kty":"RSA","use":"enc"}]}"#,
+            2,
+        );
+        let client = client(&issuer);
+        let provider = client.discover().unwrap();
+
+        let keys = client.keys(&provider).unwrap();
+
+        assert_eq!(keys.keys.len(), 2);
+        assert_eq!(keys.keys[0].kid.as_deref(), Some("k1"));
+        assert_eq!(keys.keys[1].usage.as_deref(), Some("enc"));
+        assert!(received.iter().nth(1).unwrap().starts_with("GET /realms/smartops/protocol/openid-connect/certs"));
+    }
+
+    #[test]
+    fn refresh_keeps_tokens_the_provider_does_not_rotate() {
+        let (issuer, received) = mock_provider("200 OK", r#"{"access_token":"at-2","expires_in":300}"#, 2);
+        let client = client(&issuer);
+        let provider = client.discover().unwrap();
+        let tokens = TokenSet {
+            access_token: "at-1".to_string(),
+            token_type: "Bearer".to_string(),
+            refresh_token: Some("rt".to_string()),
+            id_token: Some("it".to_string()),
+            scope: None,
+            issued_at: 700,
+            expires_at: 1_000,
+            refresh_expires_at: Some(2_000),
+        };
+
+        let fresh = client.refresh(&provider, &tokens, 1_100).unwrap();
+
+        assert_eq!(fresh.access_token, "at-2");
+        assert_eq!(fresh.expires_at, 1_400);
+        assert_eq!(fresh.refresh_token.as_deref(), Some("rt"));
+        assert_eq!(fresh.refresh_expires_at, Some(2_000));
+        assert_eq!(fresh.id_token.as_deref(), Some("it"));
+        let token_request = received.iter().nth(1).unwrap();
+        assert!(token_request.contains("grant_type=refresh_token&refresh_token=rt"));
+    }
+
+    #[test]
+    fn server_errors_are_worth_retrying() {
+        let (issuer, _) = mock_provider("503 Service Unavailable", "", 2);
+        let client = client(&issuer);
+        let provider = client.discover().unwrap();
+
+        let error = client.exchange_code(&provider, "code", "http://127.0.0.1:5000/callback", "v", 0);
+
+        assert!(matches!(error, Err(TokenError::Unavailable(_))));
+    }
+
+    #[test]
+    fn rejects_discovery_for_another_issuer() {
+        let (issuer, _) = mock_provider("200 OK", "{}", 1);
+
+        assert!(client(&format!("{}-other", issuer)).discover().is_err());
+    }
+}
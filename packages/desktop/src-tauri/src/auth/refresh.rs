@@ -0,0 +1,298 @@
+use log::{error, info, warn};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+use super::oidc::{Provider, TokenError, TokenSet};
+use super::Session;
+
+/// Access tokens are refreshed this many seconds before they expire, or
+/// halfway through their lifetime if they live shorter than twice that.
+const REFRESH_AHEAD: u64 = 60;
+
+/// Shortest pause after a successful refresh, so tokens that are issued
+/// already close to their end cannot make the thread hammer the provider.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// `auth-session-expiring` is sent this many seconds before a session that
+/// cannot be renewed ends.
+const EXPIRING_NOTICE: u64 = 120;
+
+/// Delay before the first retry of a failed refresh; doubles up to `MAX_BACKOFF`.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Longest sleep between checks, so a laptop waking from sleep catches up quickly.
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// The refresh thread of each tenant with a session, keyed by tenant id.
+#[derive(Default)]
+pub struct RefreshTasks(Mutex<HashMap<String, Sender<()>>>);
+
+/// What to do next with a tenant's tokens.
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    Refresh,
+    /// Nothing to do for this many seconds.
+    Wait(u64),
+    /// The access token has expired and cannot be renewed.
+    Expired,
+}
+
+fn next_step(tokens: &TokenSet, now: u64) -> Step {
+    let renewable_until = match (&tokens.refresh_token, tokens.refresh_expires_at) {
+        (None, _) => None,
+        (Some(_), Some(at)) if at <= now => None,
+        (Some(_), at) => Some(at.unwrap_or(u64::MAX)),
+    };
+
+    let Some(renewable_until) = renewable_until else {
+        return if tokens.expires_at > now {
+            Step::Wait(tokens.expires_at - now)
+        } else {
+            Step::Expired
+        };
+    };
+
+    // Refresh ahead of whichever token runs out first, but not so far ahead
+    // that a short-lived token is due again the moment it arrives.
+    let expires_at = tokens.expires_at.min(renewable_until);
+    let lifetime = expires_at.saturating_sub(tokens.issued_at);
+    let due = expires_at.saturating_sub(REFRESH_AHEAD.min(lifetime / 2));
+    if now >= due {
+        Step::Refresh
+    } else {
+        Step::Wait(due - now)
+    }
+}
+
+fn backoff(failures: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+/// Keeps a tenant's tokens fresh in the background, starting a thread if needed.
+///
+/// Call it whenever the tenant's tokens were replaced, e.g. after a login.
+pub fn schedule(app: &AppHandle, tenant_id: &str) {
+    let state = app.state::<RefreshTasks>();
+    let Ok(mut tasks) = state.0.lock() else { return };
+
+    if tasks.get(tenant_id).is_some_and(|wake| wake.send(()).is_ok()) {
+        return;
+    }
+
+    let (wake, woken) = mpsc::channel();
+    let thread_app = app.clone();
+    let thread_tenant = tenant_id.to_string();
+    let result = thread::Builder::new()
+        .name(format!("token-refresh-{}", tenant_id))
+        .spawn(move || run(&thread_app, &thread_tenant, woken));
+
+    match result {
+        Ok(_) => {
+            tasks.insert(tenant_id.to_string(), wake);
+        }
+        Err(e) => error!("Failed to start token refresh for tenant {}: {}", tenant_id, e),
+    }
+}
+
+/// Stops refreshing a tenant's tokens, e.g. after logging out.
+pub fn cancel(app: &AppHandle, tenant_id: &str) {
+    if let Ok(mut tasks) = app.state::<RefreshTasks>().0.lock() {
+        tasks.remove(tenant_id);
+    }
+}
+
+fn run(app: &AppHandle, tenant_id: &str, woken: Receiver<()>) {
+    let mut provider: Option<Provider> = None;
+    let mut failures = 0;
+    let mut warned = false;
+
+    loop {
+        let Some(tokens) = super::tokens(app, tenant_id) else {
+            if stop(app, tenant_id) {
+                break;
+            }
+            continue;
+        };
+        let now = super::now();
+
+        let wait = match next_step(&tokens, now) {
+            Step::Expired => {
+                expire(app, tenant_id, "The session has ended");
+                continue;
+            }
+            Step::Wait(secs) => Duration::from_secs(secs),
+            Step::Refresh => match refresh(app, &mut provider, &tokens, now) {
+                Ok(fresh) => {
+                    if !keep(app, tenant_id, &woken, &fresh) {
+                        break;
+                    }
+                    info!("Refreshed tokens of tenant {}", tenant_id);
+                    failures = 0;
+                    warned = false;
+                    super::emit_session(app, tenant_id, "auth-token-refreshed", Session::new(tenant_id, &fresh));
+                    MIN_REFRESH_INTERVAL
+                }
+                Err(TokenError::Rejected(_)) if cancelled(&woken) => break,
+                Err(TokenError::Rejected(reason)) => {
+                    expire(app, tenant_id, &reason);
+                    continue;
+                }
+                Err(TokenError::Unavailable(reason)) => {
+                    failures += 1;
+                    warn!("Token refresh for tenant {} failed (attempt {}): {}", tenant_id, failures, reason);
+                    backoff(failures)
+                }
+            },
+        };
+
+        let renewing = failures == 0 && tokens.refresh_token.is_some();
+        if !warned && !renewing && tokens.expires_at <= now + EXPIRING_NOTICE {
+            warned = true;
+            super::emit(
+                app,
+                tenant_id,
+                "auth-session-expiring",
+                json!({ "tenantId": tenant_id, "expiresAt": tokens.expires_at }),
+            );
+        }
+
+        match woken.recv_timeout(wait.min(MAX_WAIT)) {
+            Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+fn refresh(
+    app: &AppHandle,
+    provider: &mut Option<Provider>,
+    tokens: &TokenSet,
+    now: u64,
+) -> Result<TokenSet, TokenError> {
+    let client = super::client(app).map_err(TokenError::Rejected)?;
+    let provider = match provider {
+        Some(provider) => provider,
+        None => provider.insert(client.discover().map_err(TokenError::Unavailable)?),
+    };
+    client.refresh(provider, tokens, now)
+}
+
+/// Whether `cancel` was called, e.g. by a logout while a refresh was under way.
+fn cancelled(woken: &Receiver<()>) -> bool {
+    matches!(woken.try_recv(), Err(TryRecvError::Disconnected))
+}
+
+/// Stores refreshed tokens unless the thread was cancelled meanwhile.
+///
+/// `cancel` takes the same lock, so tokens cleared by a logout cannot come back.
+fn keep(app: &AppHandle, tenant_id: &str, woken: &Receiver<()>, tokens: &TokenSet) -> bool {
+    let state = app.state::<RefreshTasks>();
+    let Ok(_tasks) = state.0.lock() else { return false };
+    if cancelled(woken) {
+        return false;
+    }
+    super::store_tokens(app, tenant_id, tokens);
+    true
+}
+
+/// Drops a session that cannot be renewed and tells the user.
+fn expire(app: &AppHandle, tenant_id: &str, reason: &str) {
+    warn!("Session of tenant {} expired: {}", tenant_id, reason);
+    super::clear_tokens(app, tenant_id);
+    super::emit(
+        app,
+        tenant_id,
+        "auth-session-expired",
+        json!({ "tenantId": tenant_id, "reason": reason }),
+    );
+
+    let result = app
+        .notification()
+        .builder()
+        .title("SmartOps session expired")
+        .body(format!("Sign in again to keep using {}.", super::tenant_name(app, tenant_id)))
+        .show();
+    if let Err(e) = result {
+        error!("Failed to show session expiry notification: {}", e);
+    }
+}
+
+/// Unregisters the thread once the tenant has no tokens left.
+///
+/// Returns false if tokens were stored again meanwhile, so the thread keeps going.
+fn stop(app: &AppHandle, tenant_id: &str) -> bool {
+    let state = app.state::<RefreshTasks>();
+    let Ok(mut tasks) = state.0.lock() else { return true };
+    // Logins store tokens before `schedule` takes this lock, so checking under it cannot miss one.
+    if super::tokens(app, tenant_id).is_some() {
+        return false;
+    }
+    tasks.remove(tenant_id);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(expires_at: u64, refresh_expires_at: Option<u64>, refresh: bool) -> TokenSet {
+        TokenSet {
+            access_token: "at".to_string(),
+            token_type: "Bearer".to_string(),
+            refresh_token: refresh.then(|| "rt".to_string()),
+            id_token: None,
+            scope: None,
+            issued_at: 0,
+            expires_at,
+            refresh_expires_at,
+        }
+    }
+
+    #[test]
+    fn refreshes_ahead_of_the_first_expiry() {
+        assert_eq!(next_step(&tokens(1_300, Some(2_800), true), 1_000), Step::Wait(240));
+        assert_eq!(next_step(&tokens(1_300, Some(2_800), true), 1_250), Step::Refresh);
+        assert_eq!(next_step(&tokens(1_300, Some(1_100), true), 1_000), Step::Wait(40));
+        assert_eq!(next_step(&tokens(1_300, None, true), 1_400), Step::Refresh);
+    }
+
+    #[test]
+    fn short_lived_tokens_are_refreshed_halfway() {
+        let issued = |expires_at, refresh_expires_at| TokenSet {
+            issued_at: 1_000,
+            ..tokens(expires_at, refresh_expires_at, true)
+        };
+
+        assert_eq!(next_step(&issued(1_030, None), 1_000), Step::Wait(15));
+        assert_eq!(next_step(&issued(1_030, None), 1_015), Step::Refresh);
+        assert_eq!(next_step(&issued(1_300, Some(1_040)), 1_000), Step::Wait(20));
+        assert_eq!(next_step(&issued(1_010, None), 1_005), Step::Refresh);
+        assert_eq!(next_step(&issued(1_120, None), 1_000), Step::Wait(60));
+        assert_eq!(next_step(&issued(1_300, None), 1_000), Step::Wait(240));
+    }
+
+    #[test]
+    fn sessions_without_a_usable_refresh_token_run_out() {
+        assert_eq!(next_step(&tokens(1_300, None, false), 1_000), Step::Wait(300));
+        assert_eq!(next_step(&tokens(1_300, None, false), 1_300), Step::Expired);
+        assert_eq!(next_step(&tokens(1_300, Some(1_200), true), 1_200), Step::Wait(100));
+        assert_eq!(next_step(&tokens(1_300, Some(1_200), true), 1_300), Step::Expired);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        assert_eq!(backoff(1), Duration::from_secs(5));
+        assert_eq!(backoff(2), Duration::from_secs(10));
+        assert_eq!(backoff(4), Duration::from_secs(40));
+        assert_eq!(backoff(20), MAX_BACKOFF);
+    }
+}
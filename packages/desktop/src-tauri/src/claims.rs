@@ -0,0 +1,78 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde_json::Value;
+
+/// Decodes the payload of a JWT without checking its signature.
+///
+/// Only use the result to pick what to show; never to grant access.
+pub fn decode_payload(token: &str) -> Result<Value, String> {
+    let mut parts = token.trim().split('.');
+    let payload = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(_), Some(payload), Some(_), None) => payload,
+        _ => return Err("token is not a JWT".to_string()),
+    };
+
+    let bytes = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|e| format!("invalid token payload: {}", e))?;
+    serde_json::from_slice(&bytes).map_err(|e| format!("invalid token payload: {}", e))
+}
+
+/// Reads a string claim; `path` may use dots for nested claims, e.g. `smartops.tenant`.
+///
+/// A claim holding a list of strings yields its first entry.
+pub fn string_claim<'a>(payload: &'a Value, path: &str) -> Option<&'a str> {
+    // Keycloak mappers may emit a flat claim whose name contains dots, so try that first.
+    let value = payload
+        .get(path)
+        .or_else(|| path.split('.').try_fold(payload, |value, key| value.get(key)))?;
+
+    match value {
+        Value::String(s) => Some(s.as_str()),
+        Value::Array(items) => items.first()?.as_str(),
+        _ => None,
+    }
+    .filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn token(payload: &Value) -> String {
+        format!(
+            "eyJhbGciOiJSUzI1NiJ9.{}.c2lnbmF0dXJl",
+            URL_SAFE_NO_PAD.encode(payload.to_string())
+        )
+    }
+
+    #[test]
+    fn decodes_payload() {
+        let payload = decode_payload(&token(&json!({ "sub": "alice", "tenantId": "acme" }))).unwrap();
+
+        assert_eq!(payload["sub"], "alice");
+        assert!(decode_payload("not-a-token").is_err());
+        assert!(decode_payload("a.b.c.d").is_err());
+    }
+
+    #[test]
+    fn reads_flat_nested_and_list_claims() {
+        let payload = json!({
+            "tenantId": "acme",
+            "smartops": { "tenant": "globex" },
+            "org.tenant": "initech",
+            "tenants": ["umbrella", "acme"],
+            "empty": "",
+            "count": 3
+        });
+
+        assert_eq!(string_claim(&payload, "tenantId"), Some("acme"));
+        assert_eq!(string_claim(&payload, "smartops.tenant"), Some("globex"));
+        assert_eq!(string_claim(&payload, "org.tenant"), Some("initech"));
+        assert_eq!(string_claim(&payload, "tenants"), Some("umbrella"));
+        assert_eq!(string_claim(&payload, "empty"), None);
+        assert_eq!(string_claim(&payload, "count"), None);
+        assert_eq!(string_claim(&payload, "missing"), None);
+    }
+}
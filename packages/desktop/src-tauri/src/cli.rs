@@ -0,0 +1,148 @@
+use clap::Parser;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static ARGS: OnceLock<Cli> = OnceLock::new();
+
+/// Command-line options. Flags take precedence over the matching `DESKTOP_*` variables.
+#[derive(Debug, Clone, Default, Parser)]
+#[command(name = "smartops-desktop", version, about = "SmartOps Desktop")]
+pub struct Cli {
+    /// Config environment to load, e.g. `dev` or `prod` (overrides `DESKTOP_ENV`)
+    #[arg(long, value_name = "ENV")]
+    pub env: Option<String>,
+
+    /// Tenant to open (overrides `DESKTOP_TENANT`)
+    #[arg(long, value_name = "ID")]
+    pub tenant: Option<String>,
+
+    /// Load this URL for the selected tenant instead of its configured `appUrl`
+    #[arg(long, value_name = "URL")]
+    pub app_url: Option<String>,
+
+    /// Use this file instead of the bundled config; user and system layers still apply
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Start in the tray without showing the main window
+    #[arg(long, conflicts_with = "minimized")]
+    pub hidden: bool,
+
+    /// Start with the main window minimized
+    #[arg(long)]
+    pub minimized: bool,
+
+    /// Log filter, e.g. `debug` or `smartops_desktop_lib=trace` (overrides `RUST_LOG`)
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Print the resolved config and exit without opening a window
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Rewrite outdated user and system config files in the current schema, keeping a backup
+    #[arg(long)]
+    pub migrate_config: bool,
+
+    /// `smartops://` link to open; Windows and Linux pass clicked links this way
+    #[arg(value_name = "LINK")]
+    pub link: Option<String>,
+}
+
+impl Cli {
+    /// `--env`, else the `DESKTOP_ENV` variable that `var` looks up, else `dev`.
+    pub fn selected_env(&self, var: impl Fn(&str) -> Option<String>) -> String {
+        self.env
+            .clone()
+            .or_else(|| var("DESKTOP_ENV"))
+            .unwrap_or_else(|| "dev".to_string())
+    }
+
+    /// `--tenant`, else the `DESKTOP_TENANT` variable that `var` looks up.
+    pub fn requested_tenant(&self, var: impl Fn(&str) -> Option<String>) -> Option<String> {
+        self.tenant.clone().or_else(|| var("DESKTOP_TENANT"))
+    }
+
+    /// True when this run only reports and exits, so it must not claim the
+    /// single instance or open a window.
+    pub fn exits_early(&self) -> bool {
+        self.print_config
+    }
+}
+
+/// Parses the process arguments once; `--help`, `--version` and invalid flags exit here.
+pub fn init() -> &'static Cli {
+    ARGS.get_or_init(Cli::parse)
+}
+
+/// The parsed arguments, or the defaults if `init` has not run (e.g. in tests).
+pub fn args() -> &'static Cli {
+    ARGS.get_or_init(Cli::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::error::ErrorKind;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("smartops-desktop").chain(args.iter().copied()))
+    }
+
+    fn vars(name: &str) -> Option<String> {
+        match name {
+            "DESKTOP_ENV" => Some("staging".to_string()),
+            "DESKTOP_TENANT" => Some("acme".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn flags_beat_env_vars() {
+        let flags = parse(&["--env", "prod", "--tenant", "demo"]).unwrap();
+        assert_eq!(flags.selected_env(vars), "prod");
+        assert_eq!(flags.requested_tenant(vars).as_deref(), Some("demo"));
+
+        let bare = parse(&[]).unwrap();
+        assert_eq!(bare.selected_env(vars), "staging");
+        assert_eq!(bare.requested_tenant(vars).as_deref(), Some("acme"));
+        assert_eq!(bare.selected_env(|_| None), "dev");
+        assert_eq!(bare.requested_tenant(|_| None), None);
+    }
+
+    #[test]
+    fn hidden_and_minimized_exclude_each_other() {
+        assert!(parse(&["--hidden"]).unwrap().hidden);
+        assert!(parse(&["--minimized"]).unwrap().minimized);
+
+        let error = parse(&["--hidden", "--minimized"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn config_takes_a_path_as_given() {
+        let absolute = parse(&["--config", "/etc/smartops/custom dev.yaml"]).unwrap();
+        assert_eq!(absolute.config, Some(PathBuf::from("/etc/smartops/custom dev.yaml")));
+
+        let relative = parse(&["--config=config/dev.toml"]).unwrap();
+        assert_eq!(relative.config, Some(PathBuf::from("config/dev.toml")));
+
+        assert_eq!(parse(&["--config"]).unwrap_err().kind(), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn print_config_exits_before_any_window() {
+        let print = parse(&["--print-config", "--env", "prod"]).unwrap();
+        assert!(print.exits_early());
+        assert_eq!(print.selected_env(|_| None), "prod");
+
+        assert!(!parse(&["--hidden"]).unwrap().exits_early());
+        assert!(!parse(&["smartops://acme/open"]).unwrap().exits_early());
+    }
+
+    #[test]
+    fn takes_a_link_as_positional_argument() {
+        let cli = parse(&["--minimized", "smartops://acme/open?path=/reports"]).unwrap();
+        assert_eq!(cli.link.as_deref(), Some("smartops://acme/open?path=/reports"));
+    }
+}
@@ -0,0 +1,134 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+use super::DesktopConfig;
+
+/// A value that differs between two configs, keyed by its dotted path (e.g. `tenants.acme.appUrl`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigChange {
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+/// Lists every leaf value that was added, removed or changed.
+pub fn diff(old: &DesktopConfig, new: &DesktopConfig) -> Vec<ConfigChange> {
+    let old = serde_json::to_value(old).unwrap_or(Value::Null);
+    let new = serde_json::to_value(new).unwrap_or(Value::Null);
+
+    let mut changes = Vec::new();
+    diff_values("", Some(&old), Some(&new), &mut changes);
+    changes
+}
+
+/// The changes `tenant_id`'s window may see: everything outside `tenants` and
+/// its own tenant's values, but never another tenant's.
+pub fn visible_to(changes: &[ConfigChange], tenant_id: &str) -> Vec<ConfigChange> {
+    changes
+        .iter()
+        .filter(|change| tenant_of(change).is_none_or(|id| id == tenant_id))
+        .cloned()
+        .collect()
+}
+
+/// The tenant whose value `change` is. Tenant values are not nested, so the id
+/// is everything up to the last dot, even if it contains dots itself.
+fn tenant_of(change: &ConfigChange) -> Option<&str> {
+    let (id, _) = change.path.strip_prefix("tenants.")?.rsplit_once('.')?;
+    Some(id)
+}
+
+fn diff_values(path: &str, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<ConfigChange>) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let child = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff_values(&child, old.get(key), new.get(key), changes);
+            }
+        }
+        (Some(Value::Object(old)), None) => {
+            for (key, value) in old {
+                diff_values(&format!("{}.{}", path, key), Some(value), None, changes);
+            }
+        }
+        (None, Some(Value::Object(new))) => {
+            for (key, value) in new {
+                diff_values(&format!("{}.{}", path, key), None, Some(value), changes);
+            }
+        }
+        (old, new) if old != new => changes.push(ConfigChange {
+            path: path.to_string(),
+            old: old.cloned(),
+            new: new.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(value: Value) -> DesktopConfig {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn reports_changed_added_and_removed_tenant_values() {
+        let old = config(json!({
+            "env": "dev",
+            "defaultTenant": "default",
+            "tenants": {
+                "default": { "appUrl": "http://localhost:3000" },
+                "legacy": { "appUrl": "https://legacy.example.com" }
+            },
+            "keycloak": { "tenantClaim": "tenantId" }
+        }));
+        let new = config(json!({
+            "env": "dev",
+            "defaultTenant": "default",
+            "tenants": {
+                "default": { "appUrl": "http://localhost:3000" },
+                "acme": { "appUrl": "https://acme.example.com" }
+            },
+            "keycloak": { "tenantClaim": "org" }
+        }));
+
+        let paths: Vec<String> = diff(&old, &new).into_iter().map(|c| c.path).collect();
+        assert_eq!(
+            paths,
+            [
+                "keycloak.tenantClaim",
+                "tenants.acme.appUrl",
+                "tenants.legacy.appUrl",
+            ]
+        );
+    }
+
+    #[test]
+    fn tenants_only_see_their_own_changes() {
+        let change = |path: &str| ConfigChange {
+            path: path.to_string(),
+            old: None,
+            new: Some(json!("x")),
+        };
+        let changes = [
+            change("keycloak.tenantClaim"),
+            change("tenants.acme.appUrl"),
+            change("tenants.acme.eu.name"),
+            change("tenants.globex.healthUrl"),
+        ];
+
+        let paths = |tenant_id| visible_to(&changes, tenant_id).into_iter().map(|c| c.path).collect::<Vec<_>>();
+        assert_eq!(paths("acme"), ["keycloak.tenantClaim", "tenants.acme.appUrl"]);
+        assert_eq!(paths("acme.eu"), ["keycloak.tenantClaim", "tenants.acme.eu.name"]);
+        assert_eq!(paths("initech"), ["keycloak.tenantClaim"]);
+    }
+}
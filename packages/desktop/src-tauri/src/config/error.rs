@@ -0,0 +1,86 @@
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+
+use super::ConfigIssue;
+
+/// Why the desktop config could not be loaded.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ConfigError {
+    /// None of the config layers exist on disk.
+    NotFound { searched: Vec<PathBuf> },
+    /// A config file exists but could not be read.
+    Read { path: PathBuf, message: String },
+    /// A config file is not valid JSON, YAML or TOML.
+    Parse { path: PathBuf, message: String },
+    /// A config file has a `schemaVersion` this build cannot migrate.
+    Migration { path: PathBuf, message: String },
+    /// A config file uses `${VAR}` placeholders that cannot be resolved.
+    Substitution { path: PathBuf, issues: Vec<ConfigIssue> },
+    /// The merged config does not match the schema or its rules.
+    Invalid { issues: Vec<ConfigIssue> },
+    /// The requested tenant is not defined in the config.
+    UnknownTenant { tenant_id: String, known: Vec<String> },
+}
+
+impl ConfigError {
+    /// The file the error points at, when there is one.
+    pub fn path(&self) -> Option<&PathBuf> {
+        match self {
+            ConfigError::Read { path, .. }
+            | ConfigError::Parse { path, .. }
+            | ConfigError::Migration { path, .. }
+            | ConfigError::Substitution { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::NotFound { searched } => {
+                let searched: Vec<String> = searched.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "No config file found (searched: {})", searched.join(", "))
+            }
+            ConfigError::Read { path, message } => {
+                write!(f, "Failed to read config file {}: {}", path.display(), message)
+            }
+            ConfigError::Parse { path, message } => {
+                write!(f, "Failed to parse config file {}: {}", path.display(), message)
+            }
+            ConfigError::Migration { path, message } => {
+                write!(f, "Failed to migrate config file {}: {}", path.display(), message)
+            }
+            ConfigError::Substitution { path, issues } => {
+                write!(f, "Unresolved variables in config file {}:", path.display())?;
+                for issue in issues {
+                    write!(f, "\n  {}", issue)?;
+                }
+                Ok(())
+            }
+            ConfigError::Invalid { issues } => {
+                write!(f, "Invalid config:")?;
+                for issue in issues {
+                    write!(f, "\n  {}", issue)?;
+                }
+                Ok(())
+            }
+            ConfigError::UnknownTenant { tenant_id, known } => write!(
+                f,
+                "Tenant `{}` is not configured (known tenants: {})",
+                tenant_id,
+                known.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<ConfigError> for String {
+    fn from(error: ConfigError) -> Self {
+        error.to_string()
+    }
+}
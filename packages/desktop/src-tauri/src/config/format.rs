@@ -0,0 +1,44 @@
+use serde_json::Value;
+use std::path::Path;
+
+/// File formats a config layer can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Extensions tried for each layer, in order of preference.
+    pub const EXTENSIONS: &'static [&'static str] = &["json", "yaml", "yml", "toml"];
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            "toml" => Some(ConfigFormat::Toml),
+            _ => None,
+        }
+    }
+
+    /// Parses `raw` into a JSON document so all formats merge the same way.
+    pub fn parse(self, raw: &str) -> Result<Value, String> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(raw).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml_ng::from_str(raw).map_err(|e| e.to_string()),
+            ConfigFormat::Toml => toml::from_str(raw).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Writes `value` back out in this format.
+    pub fn serialize(self, value: &Value) -> Result<String, String> {
+        match self {
+            ConfigFormat::Json => serde_json::to_string_pretty(value)
+                .map(|json| json + "\n")
+                .map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml_ng::to_string(value).map_err(|e| e.to_string()),
+            ConfigFormat::Toml => toml::to_string_pretty(value).map_err(|e| e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::ConfigIssue;
+
+/// Replaces `${VAR}` and `${VAR:default}` placeholders in every string of `value`.
+///
+/// This follows the syntax used by the backend `app-config*.yaml` files:
+/// - `${VAR}` requires `VAR` to be set and non-empty.
+/// - `${VAR:default}` falls back to `default` (which may be empty).
+/// - `$${` is an escaped, literal `${`.
+pub fn substitute(value: &mut Value, vars: &HashMap<String, String>) -> Result<(), Vec<ConfigIssue>> {
+    let mut issues = Vec::new();
+    substitute_at(value, "$", vars, &mut issues);
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+fn substitute_at(value: &mut Value, path: &str, vars: &HashMap<String, String>, issues: &mut Vec<ConfigIssue>) {
+    match value {
+        Value::String(s) => match interpolate(s, vars) {
+            Ok(resolved) => *s = resolved,
+            Err(message) => issues.push(ConfigIssue {
+                path: path.to_string(),
+                message,
+            }),
+        },
+        Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                substitute_at(item, &format!("{}[{}]", path, i), vars, issues);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                substitute_at(item, &format!("{}.{}", path, key), vars, issues);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn interpolate(input: &str, vars: &HashMap<String, String>) -> Result<String, String> {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+
+        if let Some(after) = tail.strip_prefix("$${") {
+            out.push_str("${");
+            rest = after;
+            continue;
+        }
+
+        let Some(expr) = tail.strip_prefix("${") else {
+            out.push('$');
+            rest = &tail[1..];
+            continue;
+        };
+
+        let end = expr
+            .find('}')
+            .ok_or_else(|| format!("unterminated placeholder in `{}`", input))?;
+        let (name, default) = match expr[..end].split_once(':') {
+            Some((name, default)) => (name.trim(), Some(default)),
+            None => (expr[..end].trim(), None),
+        };
+
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid variable name `{}` in `{}`", name, input));
+        }
+
+        match (vars.get(name).filter(|v| !v.is_empty()), default) {
+            (Some(value), _) => out.push_str(value),
+            (None, Some(default)) => out.push_str(default),
+            (None, None) => {
+                return Err(format!(
+                    "environment variable `{}` is required but not set",
+                    name
+                ))
+            }
+        }
+
+        rest = &expr[end + 1..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn resolves_values_and_defaults() {
+        let vars = vars(&[("ACME_HOST", "acme.example.com"), ("EMPTY", "")]);
+
+        assert_eq!(interpolate("https://${ACME_HOST}/app", &vars).unwrap(), "https://acme.example.com/app");
+        assert_eq!(interpolate("${MISSING:http://localhost:3000}", &vars).unwrap(), "http://localhost:3000");
+        assert_eq!(interpolate("${EMPTY:fallback}", &vars).unwrap(), "fallback");
+        assert_eq!(interpolate("${MISSING:}", &vars).unwrap(), "");
+        assert_eq!(interpolate("cost: $5 $${LITERAL}", &vars).unwrap(), "cost: $5 ${LITERAL}");
+    }
+
+    #[test]
+    fn reports_required_variables_with_paths() {
+        let mut value = json!({
+            "tenants": { "acme": { "appUrl": "${ACME_URL}" } },
+            "env": "${DESKTOP_ENV_NAME:dev}"
+        });
+
+        let issues = substitute(&mut value, &HashMap::new()).unwrap_err();
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].path, "$.tenants.acme.appUrl");
+        assert!(issues[0].message.contains("ACME_URL"));
+        assert_eq!(value["env"], "dev");
+    }
+
+    #[test]
+    fn rejects_malformed_placeholders() {
+        assert!(interpolate("${UNTERMINATED", &HashMap::new()).is_err());
+        assert!(interpolate("${bad-name}", &HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,476 @@
+use log::{debug, info};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::remote::RemoteStatus;
+use super::{ConfigError, ConfigFormat, DesktopConfig};
+
+/// Prefix shared by every environment variable that overrides a config value.
+const ENV_PREFIX: &str = "DESKTOP_";
+
+/// A source that contributes values to the effective `DesktopConfig`.
+///
+/// Layers are applied in declaration order, so later layers win.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigLayer {
+    /// `config/<env>.json` shipped in the Tauri resource directory.
+    Bundled,
+    /// Signed tenant catalogue fetched from the backend (or its cache).
+    Remote,
+    /// `<app config dir>/<env>.json` written by the user.
+    User,
+    /// System-wide file managed by an administrator.
+    System,
+    /// `DESKTOP_*` environment variables.
+    Env,
+    /// Command-line flags such as `--app-url`.
+    Cli,
+}
+
+impl ConfigLayer {
+    /// True for layers whose values the user cannot change from the app, either
+    /// because an administrator owns them or because they shadow the user file.
+    pub fn is_managed(self) -> bool {
+        matches!(
+            self,
+            ConfigLayer::Remote | ConfigLayer::System | ConfigLayer::Env | ConfigLayer::Cli
+        )
+    }
+}
+
+/// A config file that was considered while loading.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerFile {
+    pub layer: ConfigLayer,
+    pub path: PathBuf,
+    pub present: bool,
+    /// Schema version the file was migrated from, if it was outdated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migrated_from: Option<u32>,
+}
+
+/// Candidate file locations for each layer, lowest precedence first.
+///
+/// Within a layer the first existing file is used.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerPaths {
+    pub bundled: Vec<PathBuf>,
+    pub user: Vec<PathBuf>,
+    pub system: Vec<PathBuf>,
+}
+
+impl LayerPaths {
+    /// Every candidate location, e.g. for watching.
+    pub fn all(&self) -> impl Iterator<Item = &PathBuf> {
+        self.bundled.iter().chain(&self.user).chain(&self.system)
+    }
+}
+
+/// The merged config together with where each value came from.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadedConfig {
+    pub config: DesktopConfig,
+    pub files: Vec<LayerFile>,
+    /// Dotted path of every leaf value (e.g. `tenants.default.appUrl`) to the layer that set it.
+    pub origins: BTreeMap<String, ConfigLayer>,
+    /// Set when a remote tenant catalogue was applied.
+    pub remote: Option<RemoteStatus>,
+}
+
+impl LoadedConfig {
+    /// Layers that set at least one value of `tenant_id`.
+    pub fn tenant_layers(&self, tenant_id: &str) -> BTreeSet<ConfigLayer> {
+        let prefix = format!("tenants.{}.", tenant_id);
+        self.origins
+            .iter()
+            // Tenant fields are flat, so a further dot means a longer id such as `acme.eu`.
+            .filter(|(path, _)| path.strip_prefix(&prefix).is_some_and(|field| !field.contains('.')))
+            .map(|(_, layer)| *layer)
+            .collect()
+    }
+}
+
+/// Reads every layer in `paths`, resolves `${VAR}` placeholders and
+/// `DESKTOP_*` overrides from `vars`, and deserializes the result.
+///
+/// `remote` receives the merged local layers and may return a remote tenant
+/// catalogue, which is applied just above the bundled layer.
+pub fn load_layered<I, R>(paths: &LayerPaths, vars: I, remote: R) -> Result<LoadedConfig, ConfigError>
+where
+    I: IntoIterator<Item = (String, String)>,
+    R: FnOnce(&Value) -> Option<(Value, RemoteStatus)>,
+{
+    let vars: HashMap<String, String> = vars.into_iter().collect();
+    let mut layers = Vec::new();
+    let mut files = Vec::new();
+
+    let file_layers = [
+        (ConfigLayer::Bundled, &paths.bundled),
+        (ConfigLayer::User, &paths.user),
+        (ConfigLayer::System, &paths.system),
+    ];
+
+    for (layer, candidates) in file_layers {
+        let Some(path) = candidates
+            .iter()
+            .find(|p| p.is_file())
+            .or_else(|| candidates.first())
+        else {
+            continue;
+        };
+        let value = read_layer(path, &vars)?;
+        files.push(LayerFile {
+            layer,
+            path: path.clone(),
+            present: value.is_some(),
+            migrated_from: value.as_ref().and_then(|(_, from)| *from),
+        });
+        if let Some((value, _)) = value {
+            info!("Applying {:?} config layer from: {:?}", layer, path);
+            layers.push((layer, value));
+        }
+    }
+
+    if layers.is_empty() {
+        return Err(ConfigError::NotFound {
+            searched: paths.all().cloned().collect(),
+        });
+    }
+
+    let overrides = env_overrides(&vars);
+    if !overrides.is_empty() {
+        layers.push((ConfigLayer::Env, Value::Object(overrides)));
+    }
+
+    let (local, _) = merge_layers(layers.clone());
+    let remote_status = remote(&local).map(|(value, status)| {
+        layers.push((ConfigLayer::Remote, value));
+        status
+    });
+    layers.sort_by_key(|(layer, _)| *layer);
+
+    let (merged, origins) = merge_layers(layers);
+    let config = super::parse_config(merged).map_err(|issues| ConfigError::Invalid { issues })?;
+
+    Ok(LoadedConfig {
+        config,
+        files,
+        origins,
+        remote: remote_status,
+    })
+}
+
+fn merge_layers(layers: Vec<(ConfigLayer, Value)>) -> (Value, BTreeMap<String, ConfigLayer>) {
+    let mut merged = Value::Object(Map::new());
+    let mut origins = BTreeMap::new();
+
+    for (layer, value) in layers {
+        merge(&mut merged, value, layer, "", &mut origins);
+    }
+
+    (merged, origins)
+}
+
+/// Reads, migrates and interpolates one layer file.
+///
+/// Returns the document and the schema version it was migrated from, if any.
+fn read_layer(path: &Path, vars: &HashMap<String, String>) -> Result<Option<(Value, Option<u32>)>, ConfigError> {
+    if !path.is_file() {
+        debug!("Config layer not present: {:?}", path);
+        return Ok(None);
+    }
+
+    let raw = fs::read_to_string(path).map_err(|e| ConfigError::Read {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+
+    let format = ConfigFormat::from_path(path).unwrap_or(ConfigFormat::Json);
+    let mut value = format.parse(&raw).map_err(|message| ConfigError::Parse {
+        path: path.to_path_buf(),
+        message,
+    })?;
+
+    let migrated_from = super::migrate(&mut value).map_err(|message| ConfigError::Migration {
+        path: path.to_path_buf(),
+        message,
+    })?;
+
+    super::substitute(&mut value, vars).map_err(|issues| ConfigError::Substitution {
+        path: path.to_path_buf(),
+        issues,
+    })?;
+
+    Ok(Some((value, migrated_from)))
+}
+
+/// Deep-merges `overlay` into `base`, recording the layer of every leaf it sets.
+fn merge(
+    base: &mut Value,
+    overlay: Value,
+    layer: ConfigLayer,
+    path: &str,
+    origins: &mut BTreeMap<String, ConfigLayer>,
+) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                let child = join_path(path, &key);
+                match base.get_mut(&key) {
+                    Some(existing) if existing.is_object() && value.is_object() => {
+                        merge(existing, value, layer, &child, origins);
+                    }
+                    _ => {
+                        forget_origins(&child, origins);
+                        record_origins(&value, layer, &child, origins);
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => {
+            forget_origins(path, origins);
+            record_origins(&overlay, layer, path, origins);
+            *base = overlay;
+        }
+    }
+}
+
+fn record_origins(
+    value: &Value,
+    layer: ConfigLayer,
+    path: &str,
+    origins: &mut BTreeMap<String, ConfigLayer>,
+) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                record_origins(value, layer, &join_path(path, key), origins);
+            }
+        }
+        _ => {
+            origins.insert(path.to_string(), layer);
+        }
+    }
+}
+
+fn forget_origins(path: &str, origins: &mut BTreeMap<String, ConfigLayer>) {
+    let nested = format!("{}.", path);
+    origins.retain(|key, _| key != path && !key.starts_with(&nested));
+}
+
+fn join_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+/// Builds the env layer from `DESKTOP_*` variables.
+///
+/// Supported variables:
+/// - `DESKTOP_DEFAULT_TENANT`
+/// - `DESKTOP_KEYCLOAK_TENANT_CLAIM`, `DESKTOP_KEYCLOAK_ISSUER` and `DESKTOP_KEYCLOAK_CLIENT_ID`
+/// - `DESKTOP_TENANTS__<ID>__APP_URL` and `DESKTOP_TENANTS__<ID>__NAME`
+///
+/// `DESKTOP_ENV` and `DESKTOP_TENANT` select what to load and are not config values.
+fn env_overrides(vars: &HashMap<String, String>) -> Map<String, Value> {
+    let mut root = Map::new();
+
+    for (name, value) in vars {
+        let value = value.clone();
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else { continue };
+
+        match key {
+            "DEFAULT_TENANT" => {
+                root.insert("defaultTenant".into(), Value::String(value));
+            }
+            "KEYCLOAK_TENANT_CLAIM" | "KEYCLOAK_ISSUER" | "KEYCLOAK_CLIENT_ID" => {
+                let field = match key {
+                    "KEYCLOAK_TENANT_CLAIM" => "tenantClaim",
+                    "KEYCLOAK_ISSUER" => "issuer",
+                    _ => "clientId",
+                };
+                let keycloak = root
+                    .entry("keycloak")
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if let Value::Object(keycloak) = keycloak {
+                    keycloak.insert(field.into(), Value::String(value));
+                }
+            }
+            _ => {
+                let Some(rest) = key.strip_prefix("TENANTS__") else { continue };
+                let Some((tenant_id, field)) = rest.split_once("__") else { continue };
+                let field = match field {
+                    "APP_URL" => "appUrl",
+                    "NAME" => "name",
+                    _ => continue,
+                };
+                let tenants = root
+                    .entry("tenants")
+                    .or_insert_with(|| Value::Object(Map::new()));
+                if let Value::Object(tenants) = tenants {
+                    let tenant = tenants
+                        .entry(tenant_id.to_lowercase())
+                        .or_insert_with(|| Value::Object(Map::new()));
+                    if let Value::Object(tenant) = tenant {
+                        tenant.insert(field.into(), Value::String(value));
+                    }
+                }
+            }
+        }
+    }
+
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Writes one file per layer below a fresh temp dir and returns their paths.
+    fn layer_files(name: &str, bundled: &Value, user: Option<&Value>, system: Option<&Value>) -> (PathBuf, LayerPaths) {
+        let dir = std::env::temp_dir().join(format!("smartops-layers-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let write = |layer: &str, value: Option<&Value>| {
+            let path = dir.join(layer).join("dev.json");
+            if let Some(value) = value {
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, value.to_string()).unwrap();
+            }
+            vec![path]
+        };
+        let paths = LayerPaths {
+            bundled: write("bundled", Some(bundled)),
+            user: write("user", user),
+            system: write("system", system),
+        };
+        (dir, paths)
+    }
+
+    fn bundled() -> Value {
+        json!({
+            "env": "dev",
+            "defaultTenant": "acme",
+            "tenants": { "acme": { "name": "Acme", "appUrl": "http://localhost:3000" } },
+            "keycloak": { "tenantClaim": "tenantId" }
+        })
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn later_layers_win_and_record_their_origin() {
+        let user = json!({ "tenants": { "acme": { "name": "Acme (mine)" } } });
+        let system = json!({
+            "keycloak": { "tenantClaim": "org.id" },
+            "tenants": { "demo": { "appUrl": "https://demo.example.com" } }
+        });
+        let (dir, paths) = layer_files("precedence", &bundled(), Some(&user), Some(&system));
+
+        let loaded = load_layered(
+            &paths,
+            vars(&[("DESKTOP_TENANTS__ACME__APP_URL", "https://acme.example.com")]),
+            |_| None,
+        )
+        .unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let acme = &loaded.config.tenants["acme"];
+        assert_eq!(acme.name.as_deref(), Some("Acme (mine)"));
+        assert_eq!(acme.app_url, "https://acme.example.com");
+        assert_eq!(
+            loaded.config.keycloak.as_ref().and_then(|k| k.tenant_claim.as_deref()),
+            Some("org.id")
+        );
+
+        let origin = |path: &str| loaded.origins.get(path).copied();
+        assert_eq!(origin("env"), Some(ConfigLayer::Bundled));
+        assert_eq!(origin("tenants.acme.name"), Some(ConfigLayer::User));
+        assert_eq!(origin("tenants.acme.appUrl"), Some(ConfigLayer::Env));
+        assert_eq!(origin("tenants.demo.appUrl"), Some(ConfigLayer::System));
+        assert_eq!(origin("keycloak.tenantClaim"), Some(ConfigLayer::System));
+        // Both bundled values of `acme` are overridden, so only the later layers remain.
+        assert_eq!(loaded.tenant_layers("acme"), BTreeSet::from([ConfigLayer::User, ConfigLayer::Env]));
+
+        let present: Vec<(ConfigLayer, bool)> = loaded.files.iter().map(|f| (f.layer, f.present)).collect();
+        assert_eq!(
+            present,
+            [(ConfigLayer::Bundled, true), (ConfigLayer::User, true), (ConfigLayer::System, true)]
+        );
+    }
+
+    #[test]
+    fn missing_layers_are_skipped_and_no_layer_is_an_error() {
+        let (dir, paths) = layer_files("missing", &bundled(), None, None);
+        let loaded = load_layered(&paths, vars(&[]), |_| None).unwrap();
+        fs::remove_file(&paths.bundled[0]).unwrap();
+        let error = load_layered(&paths, vars(&[]), |_| None).unwrap_err();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(loaded.files.iter().filter(|f| f.layer != ConfigLayer::Bundled).all(|f| !f.present));
+        assert!(loaded.origins.values().all(|layer| *layer == ConfigLayer::Bundled));
+        assert!(matches!(error, ConfigError::NotFound { searched } if searched.len() == 3));
+    }
+
+    #[test]
+    fn replacing_a_value_forgets_the_origins_below_it() {
+        let mut merged = Value::Object(Map::new());
+        let mut origins = BTreeMap::new();
+        let keycloak = json!({ "keycloak": { "issuer": "a", "clientId": "b" } });
+        merge(&mut merged, keycloak, ConfigLayer::Bundled, "", &mut origins);
+        merge(&mut merged, json!({ "keycloak": null }), ConfigLayer::User, "", &mut origins);
+
+        assert_eq!(merged, json!({ "keycloak": null }));
+        assert_eq!(origins, BTreeMap::from([("keycloak".to_string(), ConfigLayer::User)]));
+    }
+
+    #[test]
+    fn maps_env_vars_to_nested_keys() {
+        let vars: HashMap<String, String> = vars(&[
+            ("DESKTOP_DEFAULT_TENANT", "demo"),
+            ("DESKTOP_KEYCLOAK_ISSUER", "https://sso.example.com/realms/smartops"),
+            ("DESKTOP_KEYCLOAK_CLIENT_ID", "desktop"),
+            ("DESKTOP_TENANTS__DEMO__APP_URL", "https://demo.example.com"),
+            ("DESKTOP_TENANTS__DEMO__NAME", "Demo"),
+            ("DESKTOP_TENANTS__DEMO__COLOR", "red"),
+            ("DESKTOP_TENANTS__DEMO", "ignored"),
+            ("DESKTOP_ENV", "prod"),
+            ("DESKTOP_TENANT", "acme"),
+            ("PATH", "/usr/bin"),
+        ])
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            Value::Object(env_overrides(&vars)),
+            json!({
+                "defaultTenant": "demo",
+                "keycloak": { "issuer": "https://sso.example.com/realms/smartops", "clientId": "desktop" },
+                "tenants": { "demo": { "appUrl": "https://demo.example.com", "name": "Demo" } }
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_a_bad_env_value() {
+        let (dir, paths) = layer_files("bad-env", &bundled(), None, None);
+        let bad_url = vars(&[("DESKTOP_TENANTS__ACME__APP_URL", "ftp://acme.example.com")]);
+        let error = load_layered(&paths, bad_url, |_| None).unwrap_err();
+        let _ = fs::remove_dir_all(&dir);
+
+        let ConfigError::Invalid { issues } = error else { panic!("expected an invalid config, got {:?}", error) };
+        assert_eq!(issues[0].path, "$.tenants.acme.appUrl");
+    }
+}
@@ -0,0 +1,190 @@
+use log::info;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::ConfigFormat;
+
+/// Version written by this build; bump it together with a new entry in `MIGRATIONS`.
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+/// Version assumed for documents without `schemaVersion`.
+const UNVERSIONED: u32 = 1;
+
+type Migration = fn(&mut Map<String, Value>);
+
+/// `MIGRATIONS[i]` upgrades a document from version `i + 1` to `i + 2`.
+const MIGRATIONS: &[Migration] = &[v1_to_v2];
+
+/// Upgrades a config document to `CURRENT_SCHEMA_VERSION` in place.
+///
+/// Returns the version it started from when anything was migrated.
+pub fn migrate(doc: &mut Value) -> Result<Option<u32>, String> {
+    let Value::Object(map) = doc else {
+        return Ok(None);
+    };
+
+    let from = match map.get("schemaVersion") {
+        None => UNVERSIONED,
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| *v >= UNVERSIONED)
+            .ok_or_else(|| format!("schemaVersion must be a positive integer, got {}", version))?,
+    };
+
+    if from > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "schemaVersion {} is newer than this app supports ({}); please update SmartOps Desktop",
+            from, CURRENT_SCHEMA_VERSION
+        ));
+    }
+    if from == CURRENT_SCHEMA_VERSION {
+        return Ok(None);
+    }
+
+    for (step, migration) in MIGRATIONS.iter().enumerate().skip((from - UNVERSIONED) as usize) {
+        migration(map);
+        map.insert("schemaVersion".into(), Value::from(step as u32 + UNVERSIONED + 1));
+    }
+
+    Ok(Some(from))
+}
+
+/// Rewrites an outdated config file in the current schema, keeping `<file>.v<N>.bak`.
+///
+/// Placeholders are preserved because migration runs before substitution.
+/// Comments in YAML and TOML files are not.
+pub fn rewrite(path: &Path) -> Result<Option<PathBuf>, String> {
+    let format = ConfigFormat::from_path(path).unwrap_or(ConfigFormat::Json);
+    let raw = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut doc = format.parse(&raw)?;
+
+    let Some(from) = migrate(&mut doc)? else {
+        return Ok(None);
+    };
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", from));
+    let backup = PathBuf::from(backup);
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    fs::copy(path, &backup).map_err(|e| format!("failed to back up {}: {}", path.display(), e))?;
+    fs::write(&tmp, format.serialize(&doc)?).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())?;
+
+    info!(
+        "Migrated {} from schema v{} to v{} (backup: {})",
+        path.display(),
+        from,
+        CURRENT_SCHEMA_VERSION,
+        backup.display()
+    );
+    Ok(Some(backup))
+}
+
+/// v1 is the camelCase format the Electron shell used. Files written for the
+/// first Tauri builds used snake_case for `default_tenant` and `app_url`
+/// instead; v2 settles on camelCase everywhere and adds `schemaVersion`.
+fn v1_to_v2(doc: &mut Map<String, Value>) {
+    rename_key(doc, "default_tenant", "defaultTenant");
+
+    if let Some(Value::Object(tenants)) = doc.get_mut("tenants") {
+        for tenant in tenants.values_mut() {
+            if let Value::Object(tenant) = tenant {
+                rename_key(tenant, "app_url", "appUrl");
+            }
+        }
+    }
+
+    if let Some(Value::Object(keycloak)) = doc.get_mut("keycloak") {
+        rename_key(keycloak, "tenant_claim", "tenantClaim");
+    }
+}
+
+/// Moves `from` to `to` unless `to` is already set.
+fn rename_key(map: &mut Map<String, Value>, from: &str, to: &str) {
+    if let Some(value) = map.remove(from) {
+        map.entry(to).or_insert(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn v1_electron_format_only_gains_a_version() {
+        // `config/dev.json` as shipped with the Electron shell.
+        let mut doc = json!({
+            "env": "dev",
+            "defaultTenant": "default",
+            "tenants": { "default": { "name": "Default", "appUrl": "http://localhost:3000" } },
+            "keycloak": { "tenantClaim": "tenantId" }
+        });
+
+        assert_eq!(migrate(&mut doc), Ok(Some(1)));
+        assert_eq!(
+            doc,
+            json!({
+                "schemaVersion": 2,
+                "env": "dev",
+                "defaultTenant": "default",
+                "tenants": { "default": { "name": "Default", "appUrl": "http://localhost:3000" } },
+                "keycloak": { "tenantClaim": "tenantId" }
+            })
+        );
+    }
+
+    #[test]
+    fn v1_snake_case_tauri_format_is_renamed() {
+        // The shape the first Tauri `DesktopConfig` structs deserialized.
+        let mut doc = json!({
+            "env": "prod",
+            "default_tenant": "acme",
+            "tenants": { "acme": { "name": "Acme", "app_url": "https://acme.example.com" } },
+            "keycloak": { "tenantClaim": "tenantId" }
+        });
+
+        assert_eq!(migrate(&mut doc), Ok(Some(1)));
+        assert_eq!(doc["defaultTenant"], "acme");
+        assert_eq!(doc["tenants"]["acme"]["appUrl"], "https://acme.example.com");
+        assert!(doc.get("default_tenant").is_none());
+        assert!(doc["tenants"]["acme"].get("app_url").is_none());
+    }
+
+    #[test]
+    fn current_version_is_left_alone() {
+        let mut doc = json!({ "schemaVersion": CURRENT_SCHEMA_VERSION, "env": "dev" });
+
+        assert_eq!(migrate(&mut doc), Ok(None));
+    }
+
+    #[test]
+    fn rejects_newer_and_malformed_versions() {
+        assert!(migrate(&mut json!({ "schemaVersion": CURRENT_SCHEMA_VERSION + 1 })).is_err());
+        assert!(migrate(&mut json!({ "schemaVersion": "2" })).is_err());
+        assert!(migrate(&mut json!({ "schemaVersion": 0 })).is_err());
+    }
+
+    #[test]
+    fn rewrites_file_with_backup() {
+        let dir = std::env::temp_dir().join(format!("smartops-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dev.yaml");
+        fs::write(&path, "env: dev\ndefault_tenant: default\ntenants:\n  default:\n    app_url: ${APP_URL:http://localhost:3000}\n").unwrap();
+
+        let backup = rewrite(&path).unwrap().unwrap();
+        let migrated = ConfigFormat::Yaml.parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        let original = fs::read_to_string(&backup).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(migrated["schemaVersion"], 2);
+        assert_eq!(migrated["tenants"]["default"]["appUrl"], "${APP_URL:http://localhost:3000}");
+        assert!(original.contains("default_tenant"));
+    }
+}
@@ -0,0 +1,283 @@
+mod diff;
+mod error;
+mod format;
+mod interpolate;
+mod layers;
+mod migrate;
+pub mod remote;
+mod schema;
+pub mod user;
+pub mod watch;
+
+pub use diff::{diff, ConfigChange};
+pub use error::ConfigError;
+pub use format::ConfigFormat;
+pub use interpolate::substitute;
+pub use migrate::{migrate, CURRENT_SCHEMA_VERSION};
+pub use layers::{load_layered, ConfigLayer, LayerFile, LayerPaths, LoadedConfig};
+pub use schema::{json_schema, parse_config, validate, validate_app_url, validate_health_url, ConfigIssue};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+use crate::cli;
+
+/// A tenant the shell can load.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantConfig {
+    /// Absolute http(s) URL of the tenant's web app.
+    #[schemars(url)]
+    pub app_url: String,
+    /// Display name; defaults to the tenant id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Endpoint probed to check the tenant is reachable, absolute or relative to `appUrl`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_url: Option<String>,
+}
+
+/// Desktop shell configuration, as stored in `config/<env>.json`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DesktopConfig {
+    /// Version of this format; older files are migrated on load.
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
+    /// Environment name, e.g. `dev`, `staging` or `prod`.
+    pub env: String,
+    /// Key in `tenants` used when `DESKTOP_TENANT` is not set.
+    pub default_tenant: String,
+    /// Tenants keyed by id.
+    pub tenants: HashMap<String, TenantConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keycloak: Option<KeycloakConfig>,
+    /// Optional signed tenant catalogue merged into `tenants` at startup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_tenants: Option<RemoteTenantsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secure_store: Option<SecureStoreConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct KeycloakConfig {
+    /// Token claim holding the user's tenant id; dots address nested claims.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_claim: Option<String>,
+    /// Realm URL, e.g. `https://sso.example.com/realms/smartops`; enables login through the system browser.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(url)]
+    pub issuer: Option<String>,
+    /// Public client used for browser login; it must allow `http://127.0.0.1/*` redirects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Scopes requested at login.
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    /// What to do when the claim names a different tenant than the one shown.
+    #[serde(default)]
+    pub on_tenant_mismatch: TenantMismatch,
+    /// Realm or client role that may see every tenant's secure store keys and sign out other tenants.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_role: Option<String>,
+}
+
+/// Reaction to a token whose tenant claim differs from the window's tenant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TenantMismatch {
+    /// Ask the user whether to switch.
+    #[default]
+    Prompt,
+    /// Switch without asking.
+    Switch,
+}
+
+/// Locking of the secure store.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SecureStoreConfig {
+    /// Minutes without use after which a tenant's secrets need its passphrase or a new login; unset never locks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(range(min = 1))]
+    pub auto_lock_minutes: Option<u64>,
+}
+
+/// Where to fetch additional tenants from.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteTenantsConfig {
+    /// Catalogue URL; defaults to `/.well-known/smartops-desktop.json` on the default tenant's origin.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(url)]
+    pub url: Option<String>,
+    /// Base64-encoded Ed25519 key that must have signed the catalogue (`<url>.sig`).
+    pub public_key: String,
+    /// Request timeout in seconds.
+    #[serde(default = "default_remote_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn current_schema_version() -> u32 {
+    CURRENT_SCHEMA_VERSION
+}
+
+fn default_remote_timeout() -> u64 {
+    5
+}
+
+fn default_scopes() -> Vec<String> {
+    vec!["openid".to_string(), "profile".to_string(), "email".to_string()]
+}
+
+/// The config loaded at startup, shared with commands.
+pub struct ConfigState(pub Mutex<LoadedConfig>);
+
+impl DesktopConfig {
+    /// Looks up `tenant_id`.
+    pub fn tenant(&self, tenant_id: &str) -> Result<&TenantConfig, ConfigError> {
+        self.tenants.get(tenant_id).ok_or_else(|| {
+            let mut known: Vec<String> = self.tenants.keys().cloned().collect();
+            known.sort();
+            ConfigError::UnknownTenant {
+                tenant_id: tenant_id.to_string(),
+                known,
+            }
+        })
+    }
+
+    /// Returns the tenant selected through `--tenant` or `DESKTOP_TENANT`, or `default_tenant`.
+    pub fn selected_tenant_id(&self) -> String {
+        requested_tenant_id().unwrap_or_else(|| self.default_tenant.clone())
+    }
+}
+
+/// Returns the tenant explicitly requested through `--tenant` or `DESKTOP_TENANT`.
+pub fn requested_tenant_id() -> Option<String> {
+    cli::args().requested_tenant(|name| std::env::var(name).ok())
+}
+
+/// Returns the environment selected through `--env` or `DESKTOP_ENV`.
+pub fn current_env() -> String {
+    cli::args().selected_env(|name| std::env::var(name).ok())
+}
+
+/// Resolves where each config layer lives for `env`.
+pub fn layer_paths(app: &AppHandle, env: &str) -> LayerPaths {
+    let mut bundled = Vec::new();
+
+    if let Some(path) = &cli::args().config {
+        bundled.push(path.clone());
+    } else if let Ok(resource_dir) = app.path().resource_dir() {
+        bundled.extend(candidates(&resource_dir.join("config"), env));
+        // `../config/*` in `bundle.resources` is copied to `_up_/config`.
+        bundled.extend(candidates(&resource_dir.join("_up_").join("config"), env));
+    }
+
+    if let Some(exe_dir) = std::env::current_exe()
+        .ok()
+        .filter(|_| cli::args().config.is_none())
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+    {
+        bundled.extend(candidates(&exe_dir.join("config"), env));
+    }
+
+    LayerPaths {
+        bundled,
+        user: app
+            .path()
+            .app_config_dir()
+            .map(|dir| candidates(&dir, env))
+            .unwrap_or_default(),
+        system: system_config_dir()
+            .map(|dir| candidates(&dir, env))
+            .unwrap_or_default(),
+    }
+}
+
+/// `<dir>/<env>.<ext>` for every supported format.
+fn candidates(dir: &Path, env: &str) -> Vec<PathBuf> {
+    ConfigFormat::EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", env, ext)))
+        .collect()
+}
+
+/// Loads the layered config for `env` using the app's resource and config dirs.
+pub fn load(app: &AppHandle, env: &str) -> Result<LoadedConfig, ConfigError> {
+    let cache = app
+        .path()
+        .app_cache_dir()
+        .ok()
+        .map(|dir| dir.join(format!("remote-tenants-{}.json", env)));
+
+    let mut loaded = load_layered(&layer_paths(app, env), std::env::vars(), |merged| {
+        remote::resolve(merged, cache.as_deref())
+    })?;
+
+    if let Some(app_url) = &cli::args().app_url {
+        apply_app_url_override(&mut loaded, app_url)?;
+    }
+
+    if cli::args().migrate_config {
+        rewrite_migrated_files(&loaded);
+    }
+
+    Ok(loaded)
+}
+
+/// Persists in-memory migrations for `--migrate-config`; bundled files are never touched.
+fn rewrite_migrated_files(loaded: &LoadedConfig) {
+    let outdated = loaded
+        .files
+        .iter()
+        .filter(|f| f.migrated_from.is_some())
+        .filter(|f| matches!(f.layer, ConfigLayer::User | ConfigLayer::System));
+
+    for file in outdated {
+        if let Err(e) = migrate::rewrite(&file.path) {
+            log::warn!("Failed to rewrite {}: {}", file.path.display(), e);
+        }
+    }
+}
+
+/// Points the selected tenant at `--app-url`.
+fn apply_app_url_override(loaded: &mut LoadedConfig, app_url: &str) -> Result<(), ConfigError> {
+    validate_app_url(app_url).map_err(|message| ConfigError::Invalid {
+        issues: vec![ConfigIssue {
+            path: "--app-url".to_string(),
+            message,
+        }],
+    })?;
+
+    let tenant_id = loaded.config.selected_tenant_id();
+    loaded.config.tenant(&tenant_id)?;
+    if let Some(tenant) = loaded.config.tenants.get_mut(&tenant_id) {
+        tenant.app_url = app_url.to_string();
+    }
+    loaded
+        .origins
+        .insert(format!("tenants.{}.appUrl", tenant_id), ConfigLayer::Cli);
+
+    Ok(())
+}
+
+fn system_config_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var_os("ProgramData").map(|dir| PathBuf::from(dir).join("SmartOps").join("Desktop"))
+    }
+    #[cfg(target_os = "macos")]
+    {
+        Some(PathBuf::from("/Library/Application Support/SmartOps/Desktop"))
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        Some(PathBuf::from("/etc/smartops/desktop"))
+    }
+}
@@ -0,0 +1,312 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+use super::RemoteTenantsConfig;
+
+/// Catalogue location used when `remoteTenants.url` is not set.
+pub const WELL_KNOWN_PATH: &str = "/.well-known/smartops-desktop.json";
+
+/// Where the remote tenants in the current config came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteSource {
+    Network,
+    Cache,
+}
+
+/// Outcome of the last remote catalogue load, for support and diagnostics.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteStatus {
+    pub url: String,
+    pub source: RemoteSource,
+    /// Unix timestamp of when the catalogue was downloaded.
+    pub fetched_at: u64,
+    /// Why the network fetch failed, when the cache was used instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The last verified catalogue, stored with its signature so it is re-verified on load.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedCatalogue {
+    url: String,
+    fetched_at: u64,
+    body: String,
+    signature: String,
+}
+
+/// Loads the remote tenant catalogue configured in a merged (not yet validated) config.
+///
+/// Returns a `{ "tenants": ... }` document to merge as the remote layer, or `None` when
+/// remote tenants are disabled or neither the network nor the cache is usable.
+pub fn resolve(merged: &Value, cache_path: Option<&Path>) -> Option<(Value, RemoteStatus)> {
+    let settings: RemoteTenantsConfig = serde_json::from_value(merged.get("remoteTenants")?.clone()).ok()?;
+
+    let default_app_url = merged
+        .get("defaultTenant")
+        .and_then(Value::as_str)
+        .and_then(|id| merged.get("tenants")?.get(id)?.get("appUrl")?.as_str());
+
+    let Some(url) = catalogue_url(&settings, default_app_url) else {
+        warn!("Remote tenants are enabled but no catalogue URL could be determined");
+        return None;
+    };
+
+    match load_catalogue(&settings, &url, cache_path) {
+        Ok(result) => Some(result),
+        Err(e) => {
+            warn!("Remote tenants unavailable: {}", e);
+            None
+        }
+    }
+}
+
+/// Returns the configured catalogue URL, or the well-known path on the default tenant's origin.
+pub fn catalogue_url(settings: &RemoteTenantsConfig, default_app_url: Option<&str>) -> Option<String> {
+    if let Some(url) = &settings.url {
+        return Some(url.clone());
+    }
+
+    let base = Url::parse(default_app_url?).ok()?;
+    base.join(WELL_KNOWN_PATH).ok().map(String::from)
+}
+
+/// Fetches and verifies the catalogue, falling back to the verified cache when offline.
+pub fn load_catalogue(
+    settings: &RemoteTenantsConfig,
+    url: &str,
+    cache_path: Option<&Path>,
+) -> Result<(Value, RemoteStatus), String> {
+    let fetch_error = match fetch(url, Duration::from_secs(settings.timeout_secs)) {
+        Ok((body, signature)) => match verify(&body, &signature, &settings.public_key) {
+            Ok(()) => {
+                let tenants = parse_tenants(&body)?;
+                let fetched_at = now();
+                info!("Loaded remote tenants from {}", url);
+
+                if let Some(cache_path) = cache_path {
+                    write_cache(
+                        cache_path,
+                        &CachedCatalogue {
+                            url: url.to_string(),
+                            fetched_at,
+                            body,
+                            signature,
+                        },
+                    );
+                }
+
+                let status = RemoteStatus {
+                    url: url.to_string(),
+                    source: RemoteSource::Network,
+                    fetched_at,
+                    error: None,
+                };
+                return Ok((tenants, status));
+            }
+            Err(e) => format!("signature check failed: {}", e),
+        },
+        Err(e) => e,
+    };
+
+    warn!("Failed to fetch remote tenants from {}: {}", url, fetch_error);
+
+    let cached = cache_path
+        .and_then(read_cache)
+        .filter(|c| c.url == url)
+        .ok_or_else(|| format!("{} (no cached copy)", fetch_error))?;
+    verify(&cached.body, &cached.signature, &settings.public_key)
+        .map_err(|e| format!("{} (cached copy rejected: {})", fetch_error, e))?;
+
+    info!("Using cached remote tenants from {}", url);
+    let status = RemoteStatus {
+        url: url.to_string(),
+        source: RemoteSource::Cache,
+        fetched_at: cached.fetched_at,
+        error: Some(fetch_error),
+    };
+    Ok((parse_tenants(&cached.body)?, status))
+}
+
+/// Downloads the catalogue and its detached signature from `<url>.sig`.
+fn fetch(url: &str, timeout: Duration) -> Result<(String, String), String> {
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+    let get = |url: &str| {
+        agent
+            .get(url)
+            .call()
+            .map_err(|e| e.to_string())?
+            .into_string()
+            .map_err(|e| e.to_string())
+    };
+
+    let body = get(url)?;
+    let signature = get(&format!("{}.sig", url))?;
+    Ok((body, signature.trim().to_string()))
+}
+
+/// Checks a base64 Ed25519 signature of `body` against the pinned base64 public key.
+pub fn verify(body: &str, signature: &str, public_key: &str) -> Result<(), String> {
+    let key_bytes: [u8; 32] = STANDARD
+        .decode(public_key.trim())
+        .map_err(|e| format!("invalid public key: {}", e))?
+        .try_into()
+        .map_err(|_| "public key must be 32 bytes".to_string())?;
+    let key = VerifyingKey::from_bytes(&key_bytes).map_err(|e| format!("invalid public key: {}", e))?;
+
+    let signature = STANDARD
+        .decode(signature.trim())
+        .map_err(|e| format!("invalid signature encoding: {}", e))?;
+    let signature = Signature::from_slice(&signature).map_err(|e| format!("invalid signature: {}", e))?;
+
+    key.verify_strict(body.as_bytes(), &signature)
+        .map_err(|_| "signature does not match".to_string())
+}
+
+/// Keeps only `tenants` so a catalogue can never change other settings.
+fn parse_tenants(body: &str) -> Result<Value, String> {
+    let catalogue: Value = serde_json::from_str(body).map_err(|e| format!("invalid catalogue: {}", e))?;
+
+    match catalogue.get("tenants") {
+        Some(tenants @ Value::Object(_)) => Ok(json!({ "tenants": tenants })),
+        _ => Err("catalogue has no `tenants` object".to_string()),
+    }
+}
+
+fn read_cache(path: &Path) -> Option<CachedCatalogue> {
+    let raw = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+fn write_cache(path: &Path, cached: &CachedCatalogue) {
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(path, serde_json::to_vec_pretty(cached).unwrap_or_default()));
+
+    if let Err(e) = result {
+        warn!("Failed to cache remote tenants at {:?}: {}", path, e);
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    const CATALOGUE: &str = r#"{"tenants":{"acme":{"name":"Acme","appUrl":"https://acme.example.com"}},"defaultTenant":"acme"}"#;
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    fn settings(url: String) -> RemoteTenantsConfig {
+        RemoteTenantsConfig {
+            url: Some(url),
+            public_key: STANDARD.encode(signing_key().verifying_key().to_bytes()),
+            timeout_secs: 2,
+        }
+    }
+
+    /// Serves `body` at `/tenants.json` and `signature` at `/tenants.json.sig` for `requests` requests.
+    fn serve(body: &'static str, signature: String, requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/tenants.json", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                BufReader::new(&stream).read_line(&mut request_line).unwrap();
+
+                let content = if request_line.contains(".sig ") {
+                    signature.clone()
+                } else {
+                    body.to_string()
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    content.len(),
+                    content
+                )
+                .unwrap();
+            }
+        });
+
+        url
+    }
+
+    #[test]
+    fn fetches_verified_catalogue_and_keeps_only_tenants() {
+        let signature = STANDARD.encode(signing_key().sign(CATALOGUE.as_bytes()).to_bytes());
+        let url = serve(CATALOGUE, signature, 2);
+
+        let (value, status) = load_catalogue(&settings(url.clone()), &url, None).unwrap();
+
+        assert_eq!(status.source, RemoteSource::Network);
+        assert_eq!(value["tenants"]["acme"]["appUrl"], "https://acme.example.com");
+        assert!(value.get("defaultTenant").is_none());
+    }
+
+    #[test]
+    fn rejects_tampered_catalogue() {
+        let signature = STANDARD.encode(signing_key().sign(b"something else").to_bytes());
+        let url = serve(CATALOGUE, signature, 2);
+
+        let error = load_catalogue(&settings(url.clone()), &url, None).unwrap_err();
+
+        assert!(error.contains("signature"), "{}", error);
+    }
+
+    #[test]
+    fn falls_back_to_cache_when_offline() {
+        let signature = STANDARD.encode(signing_key().sign(CATALOGUE.as_bytes()).to_bytes());
+        let url = serve(CATALOGUE, signature, 2);
+        let cache = std::env::temp_dir().join(format!("smartops-remote-cache-{}.json", std::process::id()));
+
+        load_catalogue(&settings(url.clone()), &url, Some(&cache)).unwrap();
+
+        // The stub has stopped serving, so this load has to come from the cache.
+        let (value, status) = load_catalogue(&settings(url.clone()), &url, Some(&cache)).unwrap();
+        let _ = fs::remove_file(&cache);
+
+        assert_eq!(status.source, RemoteSource::Cache);
+        assert!(status.error.is_some());
+        assert_eq!(value["tenants"]["acme"]["name"], "Acme");
+    }
+
+    #[test]
+    fn derives_well_known_url_from_default_tenant() {
+        let settings = RemoteTenantsConfig {
+            url: None,
+            public_key: String::new(),
+            timeout_secs: 2,
+        };
+
+        assert_eq!(
+            catalogue_url(&settings, Some("https://ops.example.com/app/")).as_deref(),
+            Some("https://ops.example.com/.well-known/smartops-desktop.json")
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use schemars::schema::RootSchema;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use url::Url;
+
+use super::DesktopConfig;
+
+/// A single problem found in a config, located by its JSON path (e.g. `$.tenants.default.appUrl`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigIssue {
+    pub path: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Returns the JSON Schema describing the config file format.
+pub fn json_schema() -> RootSchema {
+    schemars::schema_for!(DesktopConfig)
+}
+
+/// Deserializes and validates a merged config document.
+pub fn parse_config(value: Value) -> Result<DesktopConfig, Vec<ConfigIssue>> {
+    let config: DesktopConfig = serde_path_to_error::deserialize(value).map_err(|e| {
+        let path = e.path().to_string();
+        let path = if path == "." {
+            "$".to_string()
+        } else {
+            format!("$.{}", path)
+        };
+        vec![ConfigIssue::new(path, e.into_inner().to_string())]
+    })?;
+
+    validate(&config)?;
+    Ok(config)
+}
+
+/// Checks the rules serde cannot express.
+pub fn validate(config: &DesktopConfig) -> Result<(), Vec<ConfigIssue>> {
+    let mut issues = Vec::new();
+
+    if config.env.trim().is_empty() {
+        issues.push(ConfigIssue::new("$.env", "must not be empty"));
+    }
+
+    if config.tenants.is_empty() {
+        issues.push(ConfigIssue::new("$.tenants", "at least one tenant must be configured"));
+    } else if !config.tenants.contains_key(&config.default_tenant) {
+        let mut known: Vec<&str> = config.tenants.keys().map(String::as_str).collect();
+        known.sort_unstable();
+        issues.push(ConfigIssue::new(
+            "$.defaultTenant",
+            format!(
+                "tenant `{}` is not defined (known tenants: {})",
+                config.default_tenant,
+                known.join(", ")
+            ),
+        ));
+    }
+
+    let mut tenant_ids: Vec<&String> = config.tenants.keys().collect();
+    tenant_ids.sort();
+    for id in tenant_ids {
+        let tenant = &config.tenants[id];
+        match validate_app_url(&tenant.app_url) {
+            Ok(app_url) => {
+                if let Some(health_url) = &tenant.health_url {
+                    if let Err(message) = validate_health_url(&app_url, health_url) {
+                        issues.push(ConfigIssue::new(format!("$.tenants.{}.healthUrl", id), message));
+                    }
+                }
+            }
+            Err(message) => issues.push(ConfigIssue::new(format!("$.tenants.{}.appUrl", id), message)),
+        }
+    }
+
+    if let Some(keycloak) = &config.keycloak {
+        if let Some(issuer) = &keycloak.issuer {
+            if let Err(message) = validate_app_url(issuer) {
+                issues.push(ConfigIssue::new("$.keycloak.issuer", message));
+            }
+            if keycloak.client_id.as_deref().is_none_or(|id| id.trim().is_empty()) {
+                issues.push(ConfigIssue::new("$.keycloak.clientId", "is required when `issuer` is set"));
+            }
+        }
+    }
+
+    if let Some(remote) = &config.remote_tenants {
+        if let Some(url) = &remote.url {
+            if let Err(message) = validate_app_url(url) {
+                issues.push(ConfigIssue::new("$.remoteTenants.url", message));
+            }
+        }
+        let key_len = STANDARD.decode(remote.public_key.trim()).map(|k| k.len());
+        if key_len != Ok(32) {
+            issues.push(ConfigIssue::new(
+                "$.remoteTenants.publicKey",
+                "must be a base64-encoded 32-byte Ed25519 public key",
+            ));
+        }
+    }
+
+    if let Some(secure_store) = &config.secure_store {
+        if secure_store.auto_lock_minutes == Some(0) {
+            issues.push(ConfigIssue::new("$.secureStore.autoLockMinutes", "must be at least 1"));
+        }
+    }
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+/// Requires an absolute `http` or `https` URL with a host.
+pub fn validate_app_url(app_url: &str) -> Result<Url, String> {
+    let url = Url::parse(app_url)
+        .map_err(|e| format!("`{}` is not an absolute URL: {}", app_url, e))?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "`{}` must use http or https, not `{}`",
+            app_url,
+            url.scheme()
+        ));
+    }
+
+    if !url.has_host() {
+        return Err(format!("`{}` has no host", app_url));
+    }
+
+    Ok(url)
+}
+
+/// Resolves `health_url` against the tenant's `app_url` and requires the
+/// result to be an absolute `http` or `https` URL with a host.
+pub fn validate_health_url(app_url: &Url, health_url: &str) -> Result<Url, String> {
+    let joined = app_url
+        .join(health_url)
+        .map_err(|e| format!("`{}` is not a valid health URL: {}", health_url, e))?;
+    validate_app_url(joined.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn accepts_bundled_config_format() {
+        let config = parse_config(json!({
+            "env": "dev",
+            "defaultTenant": "default",
+            "tenants": { "default": { "name": "Default", "appUrl": "http://localhost:3000" } },
+            "keycloak": { "tenantClaim": "tenantId" }
+        }))
+        .unwrap();
+
+        assert_eq!(config.tenants["default"].app_url, "http://localhost:3000");
+    }
+
+    #[test]
+    fn reports_paths_for_invalid_values() {
+        let issues = parse_config(json!({
+            "env": "dev",
+            "defaultTenant": "missing",
+            "tenants": { "acme": { "appUrl": "ftp://acme.example.com" } }
+        }))
+        .unwrap_err();
+
+        let paths: Vec<&str> = issues.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(paths, ["$.defaultTenant", "$.tenants.acme.appUrl"]);
+    }
+
+    #[test]
+    fn login_needs_a_client_id() {
+        let issues = parse_config(json!({
+            "env": "dev",
+            "defaultTenant": "default",
+            "tenants": { "default": { "appUrl": "http://localhost:3000" } },
+            "keycloak": { "issuer": "https://sso.example.com/realms/smartops" }
+        }))
+        .unwrap_err();
+
+        assert_eq!(issues[0].path, "$.keycloak.clientId");
+    }
+
+    #[test]
+    fn health_urls_must_resolve_to_http() {
+        let tenant = |health_url: &str| {
+            json!({ "appUrl": "https://acme.example.com/app/", "healthUrl": health_url })
+        };
+        let issues = parse_config(json!({
+            "env": "dev",
+            "defaultTenant": "absolute",
+            "tenants": {
+                "absolute": tenant("https://status.example.com/acme"),
+                "relative": tenant("/healthz"),
+                "file": tenant("file:///etc/passwd"),
+                "mail": tenant("mailto:ops@example.com")
+            }
+        }))
+        .unwrap_err();
+
+        let paths: Vec<&str> = issues.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(paths, ["$.tenants.file.healthUrl", "$.tenants.mail.healthUrl"]);
+    }
+
+    #[test]
+    fn auto_lock_needs_a_period() {
+        let issues = parse_config(json!({
+            "env": "dev",
+            "defaultTenant": "default",
+            "tenants": { "default": { "appUrl": "http://localhost:3000" } },
+            "secureStore": { "autoLockMinutes": 0 }
+        }))
+        .unwrap_err();
+
+        assert_eq!(issues[0].path, "$.secureStore.autoLockMinutes");
+    }
+
+    #[test]
+    fn reports_path_for_missing_field() {
+        let issues = parse_config(json!({
+            "env": "dev",
+            "defaultTenant": "default",
+            "tenants": { "default": { "name": "Default" } }
+        }))
+        .unwrap_err();
+
+        assert_eq!(issues[0].path, "$.tenants.default");
+        assert!(issues[0].message.contains("appUrl"));
+    }
+
+    /// Run with `UPDATE_SCHEMA=1` to rewrite the published schema.
+    #[test]
+    fn published_schema_is_up_to_date() {
+        let generated = serde_json::to_string_pretty(&json_schema()).unwrap() + "\n";
+
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            let path = std::path::Path::new(file!())
+                .parent()
+                .unwrap()
+                .join("../../../config/desktop-config.schema.json");
+            std::fs::write(path, &generated).unwrap();
+            return;
+        }
+
+        assert_eq!(
+            include_str!("../../../config/desktop-config.schema.json"),
+            generated,
+            "config/desktop-config.schema.json is stale, rerun this test with UPDATE_SCHEMA=1"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{migrate, validate_app_url, validate_health_url, ConfigFormat, LayerPaths, TenantConfig, CURRENT_SCHEMA_VERSION};
+
+/// The user layer file tenants are written to: the one in use, else a new `<env>.json`.
+pub fn file(paths: &LayerPaths) -> Option<&PathBuf> {
+    paths.user.iter().find(|p| p.is_file()).or_else(|| paths.user.first())
+}
+
+/// What the user file looked like before an edit, so a rejected edit can be undone.
+pub struct Backup {
+    path: PathBuf,
+    contents: Option<String>,
+}
+
+impl Backup {
+    /// Puts the previous contents back, or removes the file if the edit created it.
+    pub fn restore(self) -> Result<(), String> {
+        match self.contents {
+            Some(contents) => fs::write(&self.path, contents),
+            None => fs::remove_file(&self.path),
+        }
+        .map_err(|e| format!("failed to restore {}: {}", self.path.display(), e))
+    }
+}
+
+/// Applies `change` to the user file at `path` and writes it back in its own format.
+///
+/// The file is edited before `${VAR}` substitution, so placeholders are kept;
+/// comments in YAML and TOML files are not. Outdated files are written in the
+/// current schema.
+pub fn edit<F>(path: &Path, change: F) -> Result<Backup, String>
+where
+    F: FnOnce(&mut Map<String, Value>) -> Result<(), String>,
+{
+    let format = ConfigFormat::from_path(path).unwrap_or(ConfigFormat::Json);
+    let contents = if path.is_file() {
+        Some(fs::read_to_string(path).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+
+    let mut doc = match &contents {
+        Some(raw) => format.parse(raw)?,
+        None => Value::Object(Map::from_iter([(
+            "schemaVersion".to_string(),
+            Value::from(CURRENT_SCHEMA_VERSION),
+        )])),
+    };
+    migrate(&mut doc)?;
+    let Value::Object(map) = &mut doc else {
+        return Err(format!("{} does not contain an object", path.display()));
+    };
+    change(map)?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, format.serialize(&doc)?).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())?;
+
+    Ok(Backup {
+        path: path.to_path_buf(),
+        contents,
+    })
+}
+
+/// Sets `tenants.<tenant_id>` in a user document, replacing any earlier entry.
+pub fn set_tenant(doc: &mut Map<String, Value>, tenant_id: &str, tenant: &TenantConfig) -> Result<(), String> {
+    let value = serde_json::to_value(tenant).map_err(|e| e.to_string())?;
+    let tenants = doc
+        .entry("tenants")
+        .or_insert_with(|| Value::Object(Map::new()));
+    let Value::Object(tenants) = tenants else {
+        return Err("`tenants` in the user config is not an object".to_string());
+    };
+    tenants.insert(tenant_id.to_string(), value);
+    Ok(())
+}
+
+/// Removes `tenants.<tenant_id>` from a user document.
+pub fn remove_tenant(doc: &mut Map<String, Value>, tenant_id: &str) {
+    if let Some(Value::Object(tenants)) = doc.get_mut("tenants") {
+        tenants.remove(tenant_id);
+    }
+}
+
+/// Checks an id for a tenant added from the app.
+///
+/// Ids become config keys, env variable names and window labels, so they are
+/// kept to letters, digits, `-` and `_`.
+pub fn validate_tenant_id(tenant_id: &str) -> Result<(), String> {
+    if tenant_id.is_empty() {
+        return Err("Tenant id must not be empty".to_string());
+    }
+    if tenant_id.len() > 64 {
+        return Err(format!("Tenant id `{}` is longer than 64 characters", tenant_id));
+    }
+    if !tenant_id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+        return Err(format!(
+            "Tenant id `{}` may only contain letters, digits, `-` and `_`",
+            tenant_id
+        ));
+    }
+    Ok(())
+}
+
+/// Trims a tenant entered in the app and checks its URLs.
+pub fn normalize_tenant(tenant: TenantConfig) -> Result<TenantConfig, String> {
+    let app_url = tenant.app_url.trim().to_string();
+    let parsed = validate_app_url(&app_url)?;
+    let trimmed = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+
+    let health_url = trimmed(tenant.health_url);
+    if let Some(health_url) = &health_url {
+        validate_health_url(&parsed, health_url)?;
+    }
+
+    Ok(TenantConfig {
+        app_url,
+        name: trimmed(tenant.name),
+        health_url,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tenant(app_url: &str) -> TenantConfig {
+        TenantConfig {
+            app_url: app_url.to_string(),
+            name: None,
+            health_url: None,
+        }
+    }
+
+    #[test]
+    fn tenant_ids_are_restricted() {
+        assert!(validate_tenant_id("acme-demo_2").is_ok());
+        assert!(validate_tenant_id("").is_err());
+        assert!(validate_tenant_id("acme.demo").is_err());
+        assert!(validate_tenant_id("../acme").is_err());
+        assert!(validate_tenant_id(&"a".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn normalizes_and_checks_tenant_urls() {
+        let normalized = normalize_tenant(TenantConfig {
+            app_url: " https://demo.example.com/app ".to_string(),
+            name: Some("  ".to_string()),
+            health_url: Some("/healthz".to_string()),
+        })
+        .unwrap();
+
+        assert_eq!(normalized.app_url, "https://demo.example.com/app");
+        assert_eq!(normalized.name, None);
+        assert_eq!(normalized.health_url.as_deref(), Some("/healthz"));
+        assert!(normalize_tenant(tenant("demo.example.com")).is_err());
+        assert!(normalize_tenant(tenant("file:///etc/passwd")).is_err());
+    }
+
+    #[test]
+    fn edits_keep_format_placeholders_and_other_tenants() {
+        let dir = std::env::temp_dir().join(format!("smartops-user-{}", std::process::id()));
+        let path = dir.join("dev.yaml");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "tenants:\n  acme:\n    app_url: https://${ACME_HOST}\n").unwrap();
+
+        let backup = edit(&path, |doc| set_tenant(doc, "demo", &tenant("https://demo.example.com"))).unwrap();
+        let edited = ConfigFormat::Yaml.parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        edit(&path, |doc| {
+            remove_tenant(doc, "acme");
+            Ok(())
+        })
+        .unwrap();
+        let removed = ConfigFormat::Yaml.parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        backup.restore().unwrap();
+        let restored = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            edited,
+            json!({
+                "schemaVersion": CURRENT_SCHEMA_VERSION,
+                "tenants": {
+                    "acme": { "appUrl": "https://${ACME_HOST}" },
+                    "demo": { "appUrl": "https://demo.example.com" }
+                }
+            })
+        );
+        assert_eq!(removed["tenants"], json!({ "demo": { "appUrl": "https://demo.example.com" } }));
+        assert!(restored.contains("app_url"));
+    }
+
+    #[test]
+    fn edit_creates_missing_file_and_restore_removes_it() {
+        let dir = std::env::temp_dir().join(format!("smartops-user-new-{}", std::process::id()));
+        let path = dir.join("dev.json");
+
+        let backup = edit(&path, |doc| set_tenant(doc, "demo", &tenant("https://demo.example.com"))).unwrap();
+        let created: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        backup.restore().unwrap();
+        let exists = path.exists();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(created["schemaVersion"], CURRENT_SCHEMA_VERSION);
+        assert_eq!(created["tenants"]["demo"]["appUrl"], "https://demo.example.com");
+        assert!(!exists);
+    }
+}
@@ -0,0 +1,120 @@
+use log::{debug, error, info, warn};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::diff::visible_to;
+use super::{diff, ConfigChange, ConfigError, ConfigState};
+use crate::windows;
+
+/// How long to wait for further writes before reloading; editors often save in several steps.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches `files` on a background thread and reloads the config when one changes.
+pub fn spawn(app: AppHandle, files: Vec<PathBuf>) {
+    let result = thread::Builder::new()
+        .name("config-watcher".into())
+        .spawn(move || {
+            if let Err(e) = run(&app, &files) {
+                error!("Config watcher stopped: {}", e);
+            }
+        });
+
+    if let Err(e) = result {
+        error!("Failed to start config watcher: {}", e);
+    }
+}
+
+fn run(app: &AppHandle, files: &[PathBuf]) -> notify::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+
+    // Watch the parent directories so files that are replaced or created later are seen too.
+    let mut dirs: Vec<&Path> = files.iter().filter_map(|f| f.parent()).collect();
+    dirs.sort();
+    dirs.dedup();
+    for dir in dirs {
+        match watcher.watch(dir, RecursiveMode::NonRecursive) {
+            Ok(()) => debug!("Watching config directory: {:?}", dir),
+            Err(e) => debug!("Not watching config directory {:?}: {}", dir, e),
+        }
+    }
+
+    while let Ok(event) = rx.recv() {
+        if !touches(&event, files) {
+            continue;
+        }
+        while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+        if let Err(e) = reload(app) {
+            warn!("Rejected config change, keeping the last good config: {}", e);
+            let _ = app.emit("config-reload-failed", &e);
+        }
+    }
+
+    Ok(())
+}
+
+fn touches(event: &notify::Result<Event>, files: &[PathBuf]) -> bool {
+    match event {
+        Ok(event) => {
+            !matches!(event.kind, EventKind::Access(_))
+                && event.paths.iter().any(|p| files.contains(p))
+        }
+        Err(e) => {
+            debug!("Config watcher error: {}", e);
+            false
+        }
+    }
+}
+
+/// Reloads and validates the config, swapping it in only when it is usable.
+///
+/// Sends `config-changed` to each tenant's window when anything differs, with
+/// the changes outside `tenants` and those of its own tenant only.
+///
+/// Loading may fetch the remote catalogue, so this blocks; keep it off the main thread.
+pub fn reload(app: &AppHandle) -> Result<Vec<ConfigChange>, ConfigError> {
+    let loaded = super::load(app, &super::current_env())?;
+    let tenant_id = crate::tenant::active_id(app).unwrap_or_else(|| loaded.config.selected_tenant_id());
+    loaded.config.tenant(&tenant_id)?;
+
+    let state = app.state::<ConfigState>();
+    let (changes, tenant_ids) = match state.0.lock() {
+        Ok(mut current) => {
+            let changes = diff(&current.config, &loaded.config);
+            let tenant_ids: BTreeSet<String> =
+                current.config.tenants.keys().chain(loaded.config.tenants.keys()).cloned().collect();
+            *current = loaded;
+            (changes, tenant_ids)
+        }
+        Err(e) => {
+            error!("Config state is poisoned: {}", e);
+            return Ok(Vec::new());
+        }
+    };
+
+    if changes.is_empty() {
+        return Ok(changes);
+    }
+
+    info!("Config reloaded with {} change(s)", changes.len());
+    crate::refresh_tray(app);
+
+    for tenant_id in &tenant_ids {
+        let visible = visible_to(&changes, tenant_id);
+        if visible.is_empty() {
+            continue;
+        }
+        let payload = serde_json::json!({ "changes": visible });
+        if let Err(e) = app.emit_to(windows::label(tenant_id), "config-changed", payload) {
+            error!("Failed to emit config-changed: {}", e);
+        }
+    }
+
+    Ok(changes)
+}
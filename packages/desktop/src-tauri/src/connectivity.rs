@@ -0,0 +1,296 @@
+use log::{error, info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State, Url, WebviewWindow};
+
+use crate::config::ConfigState;
+use crate::{pages, windows};
+use crate::probe::{self, ProbeFailure};
+
+/// Page shown in a tenant window while its tenant cannot be reached.
+pub const OFFLINE_PAGE: &str = "offline.html";
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a reachable tenant is checked again.
+const ONLINE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often an unreachable tenant is retried.
+const OFFLINE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Result of the latest check of a tenant, sent with `connectivity-changed`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantStatus {
+    pub tenant_id: String,
+    pub online: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<ProbeFailure>,
+    /// Unix timestamp of this check.
+    pub checked_at: u64,
+    /// Unix timestamp of the last successful check, if any.
+    pub last_success: Option<u64>,
+}
+
+struct Watcher {
+    tenant_id: String,
+    wake: Sender<()>,
+    /// Where to go next instead of where the window was headed, see `load`.
+    pending: Option<Url>,
+}
+
+/// Latest status per tenant and the probe thread of each tenant window.
+#[derive(Default)]
+pub struct Connectivity {
+    statuses: Mutex<HashMap<String, TenantStatus>>,
+    watchers: Mutex<HashMap<String, Watcher>>,
+}
+
+/// Probes the tenant behind a window before loading `target`, then keeps
+/// checking it in the background.
+///
+/// The window should start on `OFFLINE_PAGE`, which shows progress until the
+/// first check passes. Whenever the tenant becomes unreachable the window is
+/// sent back to that page, and returns to where it was once it recovers.
+pub fn watch(app: &AppHandle, window: &WebviewWindow, tenant_id: &str, target: Url) {
+    let (wake, woken) = mpsc::channel();
+    let label = window.label().to_string();
+    if let Ok(mut watchers) = app.state::<Connectivity>().watchers.lock() {
+        watchers.insert(
+            label.clone(),
+            Watcher {
+                tenant_id: tenant_id.to_string(),
+                wake,
+                pending: None,
+            },
+        );
+    }
+
+    let thread_app = app.clone();
+    let thread_tenant = tenant_id.to_string();
+    let result = thread::Builder::new()
+        .name(format!("probe-{}", label))
+        .spawn(move || run(&thread_app, &label, &thread_tenant, target, woken));
+
+    if let Err(e) = result {
+        error!("Failed to start connectivity probe for tenant {}: {}", tenant_id, e);
+    }
+}
+
+fn run(app: &AppHandle, label: &str, tenant_id: &str, target: Url, woken: Receiver<()>) {
+    let mut resume = Some(target);
+    // Stops once the window is closed or the tenant is removed from the config.
+    while let Some(window) = app.get_webview_window(label) {
+        let Some(status) = check(app, tenant_id) else { break };
+        let pending = take_pending(app, label);
+
+        if status.online {
+            if let Some(url) = pending.or(resume.take()) {
+                if let Err(e) = window.navigate(url) {
+                    error!("Failed to load tenant {}: {}", tenant_id, e);
+                }
+            }
+        } else {
+            if resume.is_none() {
+                resume = window.url().ok().filter(|url| !pages::is_page(url));
+                let _ = window.navigate(pages::page_url(OFFLINE_PAGE));
+            }
+            if pending.is_some() {
+                resume = pending;
+            }
+        }
+
+        let interval = if status.online { ONLINE_INTERVAL } else { OFFLINE_INTERVAL };
+        record(app, status);
+
+        match woken.recv_timeout(interval) {
+            Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+/// Loads `url` in a tenant window as soon as its tenant is reachable.
+///
+/// A window that is still on `OFFLINE_PAGE` goes there instead of where it was headed.
+pub fn load(app: &AppHandle, label: &str, url: Url) -> Result<(), String> {
+    let state = app.state::<Connectivity>();
+    let mut watchers = state.watchers.lock().map_err(|e| e.to_string())?;
+    let watcher = watchers.get_mut(label).ok_or("This window is not a tenant window")?;
+    watcher.pending = Some(url);
+    watcher.wake.send(()).map_err(|e| e.to_string())
+}
+
+fn take_pending(app: &AppHandle, label: &str) -> Option<Url> {
+    app.state::<Connectivity>().watchers.lock().ok()?.get_mut(label)?.pending.take()
+}
+
+/// Stops the probe thread of a closed window.
+pub fn forget(app: &AppHandle, label: &str) {
+    if let Ok(mut watchers) = app.state::<Connectivity>().watchers.lock() {
+        watchers.remove(label);
+    }
+}
+
+/// Probes a tenant's health URL, or `None` if the tenant is no longer configured.
+fn check(app: &AppHandle, tenant_id: &str) -> Option<TenantStatus> {
+    let url = {
+        let state = app.state::<ConfigState>();
+        let loaded = state.0.lock().ok()?;
+        let tenant = loaded.config.tenants.get(tenant_id)?;
+        let app_url = Url::parse(&tenant.app_url).ok()?;
+        match &tenant.health_url {
+            Some(health_url) => app_url.join(health_url).ok()?,
+            None => app_url,
+        }
+    };
+
+    let failure = probe::probe(&url, PROBE_TIMEOUT).err();
+    let checked_at = now();
+    let previous_success = app
+        .state::<Connectivity>()
+        .statuses
+        .lock()
+        .ok()
+        .and_then(|statuses| statuses.get(tenant_id)?.last_success);
+
+    Some(TenantStatus {
+        tenant_id: tenant_id.to_string(),
+        online: failure.is_none(),
+        last_success: if failure.is_none() { Some(checked_at) } else { previous_success },
+        failure,
+        checked_at,
+    })
+}
+
+/// Stores a status; true if reachability or the failing stage changed.
+fn store(statuses: &mut HashMap<String, TenantStatus>, status: TenantStatus) -> bool {
+    let changed = statuses.get(&status.tenant_id).is_none_or(|old| {
+        old.online != status.online
+            || old.failure.as_ref().map(|f| f.stage) != status.failure.as_ref().map(|f| f.stage)
+    });
+    statuses.insert(status.tenant_id.clone(), status);
+    changed
+}
+
+/// Stores a status and sends `connectivity-changed` to the tenant's window
+/// when reachability or the failing stage changed.
+fn record(app: &AppHandle, status: TenantStatus) {
+    let state = app.state::<Connectivity>();
+    let Ok(mut statuses) = state.statuses.lock() else { return };
+    let changed = store(&mut statuses, status.clone());
+    drop(statuses);
+
+    if !changed {
+        return;
+    }
+    match &status.failure {
+        None => info!("Tenant {} is reachable", status.tenant_id),
+        Some(failure) => warn!(
+            "Tenant {} is unreachable ({:?}): {}",
+            status.tenant_id, failure.stage, failure.message
+        ),
+    }
+    if let Err(e) = app.emit_to(windows::label(&status.tenant_id), "connectivity-changed", &status) {
+        error!("Failed to emit connectivity-changed: {}", e);
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Everything the offline page shows about the calling window's tenant.
+#[tauri::command]
+pub fn get_connectivity(
+    window: WebviewWindow,
+    connectivity: State<'_, Connectivity>,
+    config: State<'_, ConfigState>,
+) -> Result<serde_json::Value, String> {
+    let tenant_id = connectivity
+        .watchers
+        .lock()
+        .map_err(|e| e.to_string())?
+        .get(window.label())
+        .map(|w| w.tenant_id.clone())
+        .ok_or("This window is not a tenant window")?;
+    let status = connectivity
+        .statuses
+        .lock()
+        .map_err(|e| e.to_string())?
+        .get(&tenant_id)
+        .cloned();
+
+    let loaded = config.0.lock().map_err(|e| e.to_string())?;
+    let tenant = loaded.config.tenant(&tenant_id)?;
+    let mut tenants: Vec<serde_json::Value> = loaded
+        .config
+        .tenants
+        .iter()
+        .filter(|(id, _)| **id != tenant_id)
+        .map(|(id, t)| serde_json::json!({ "id": id, "name": t.name.as_ref().unwrap_or(id) }))
+        .collect();
+    tenants.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+    Ok(serde_json::json!({
+        "tenantId": tenant_id,
+        "tenantName": tenant.name.as_ref().unwrap_or(&tenant_id),
+        "appUrl": tenant.app_url,
+        "status": status,
+        "tenants": tenants,
+    }))
+}
+
+/// Checks the calling window's tenant again right away.
+#[tauri::command]
+pub fn retry_connection(window: WebviewWindow, connectivity: State<'_, Connectivity>) -> Result<(), String> {
+    let watchers = connectivity.watchers.lock().map_err(|e| e.to_string())?;
+    let watcher = watchers
+        .get(window.label())
+        .ok_or("This window is not a tenant window")?;
+    watcher.wake.send(()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::ProbeStage;
+
+    fn status(tenant_id: &str, stage: Option<ProbeStage>, checked_at: u64) -> TenantStatus {
+        TenantStatus {
+            tenant_id: tenant_id.to_string(),
+            online: stage.is_none(),
+            failure: stage.map(|stage| ProbeFailure {
+                stage,
+                message: format!("failed at {}", checked_at),
+            }),
+            checked_at,
+            last_success: None,
+        }
+    }
+
+    #[test]
+    fn reports_only_changes_of_reachability_or_stage() {
+        let mut statuses = HashMap::new();
+
+        assert!(store(&mut statuses, status("acme", None, 1)));
+        assert!(!store(&mut statuses, status("acme", None, 2)));
+        assert!(store(&mut statuses, status("globex", None, 2)));
+
+        assert!(store(&mut statuses, status("acme", Some(ProbeStage::Dns), 3)));
+        assert!(!store(&mut statuses, status("acme", Some(ProbeStage::Dns), 4)));
+        assert!(store(&mut statuses, status("acme", Some(ProbeStage::Tls), 5)));
+        assert!(store(&mut statuses, status("acme", None, 6)));
+
+        // The latest check is kept even when nothing changed.
+        assert!(!store(&mut statuses, status("acme", None, 7)));
+        assert_eq!(statuses["acme"].checked_at, 7);
+    }
+}
@@ -0,0 +1,222 @@
+use log::{info, warn};
+use tauri::{AppHandle, Manager, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+
+use crate::config::{ConfigState, DesktopConfig};
+use crate::{cli, connectivity, tenant, windows};
+
+/// URI scheme registered for the app; must match `plugins.deep-link` in `tauri.conf.json`.
+pub const SCHEME: &str = "smartops";
+
+/// A parsed `smartops://<tenant>/<path>` link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeepLink {
+    pub tenant_id: String,
+    /// Path below the tenant's `appUrl`, starting with `/`.
+    pub path: String,
+    pub query: Option<String>,
+    pub fragment: Option<String>,
+}
+
+/// Parses a `smartops://` link.
+///
+/// Links carrying credentials or a port are rejected. The URL parser resolves
+/// dot segments, so a path never climbs above `/`; encoded slashes and
+/// backslashes, which servers may turn back into separators, are rejected.
+pub fn parse(link: &str) -> Result<DeepLink, String> {
+    let url = Url::parse(link.trim()).map_err(|e| format!("invalid link: {}", e))?;
+    if url.scheme() != SCHEME {
+        return Err(format!("not a {}:// link", SCHEME));
+    }
+    if !url.username().is_empty() || url.password().is_some() || url.port().is_some() {
+        return Err("links may not carry credentials or a port".to_string());
+    }
+    let tenant_id = url
+        .host_str()
+        .filter(|host| !host.is_empty())
+        .ok_or("the link names no tenant")?;
+
+    let path = match url.path() {
+        "" => "/",
+        path => path,
+    };
+    let lowercase = path.to_ascii_lowercase();
+    if path.contains('\\') || lowercase.contains("%2f") || lowercase.contains("%5c") {
+        return Err(format!("the path {} is not allowed", path));
+    }
+
+    Ok(DeepLink {
+        tenant_id: tenant_id.to_string(),
+        path: path.to_string(),
+        query: url.query().map(String::from),
+        fragment: url.fragment().map(String::from),
+    })
+}
+
+/// The page a link leads to: its path below the directory of `app_url`.
+pub fn target(app_url: &Url, link: &DeepLink) -> Url {
+    let mut url = app_url.clone();
+    let base = match app_url.path().rfind('/') {
+        Some(end) => &app_url.path()[..=end],
+        None => "/",
+    };
+    url.set_path(&format!("{}{}", base, link.path.trim_start_matches('/')));
+    url.set_query(link.query.as_deref());
+    url.set_fragment(link.fragment.as_deref());
+    url
+}
+
+/// The link the app was launched with, if any.
+fn startup_link(app: &AppHandle) -> Option<String> {
+    cli::args().link.clone().or_else(|| {
+        let urls = app.deep_link().get_current().ok()??;
+        urls.first().map(|url| url.to_string())
+    })
+}
+
+/// The configured tenant named by the launch link, so startup can open it directly.
+pub fn startup_tenant(app: &AppHandle, config: &DesktopConfig) -> Option<String> {
+    let link = parse(&startup_link(app)?).ok()?;
+    config.tenants.contains_key(&link.tenant_id).then_some(link.tenant_id)
+}
+
+/// Opens the launch link once the first tenant window is up.
+pub fn open_startup_link(app: &AppHandle) {
+    if let Some(link) = startup_link(app) {
+        open(app, &link);
+    }
+}
+
+/// Handles links clicked while the app is running.
+///
+/// macOS delivers them to the running app; on Windows and Linux they reach it
+/// through the single-instance handoff.
+pub fn listen(app: &AppHandle) {
+    #[cfg(any(windows, target_os = "linux"))]
+    if cfg!(debug_assertions) {
+        // Installers register the scheme; development builds register themselves.
+        if let Err(e) = app.deep_link().register_all() {
+            warn!("Failed to register the {}:// scheme: {}", SCHEME, e);
+        }
+    }
+
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        // Links arriving before startup completes are picked up by `open_startup_link`.
+        if handle.try_state::<ConfigState>().is_none() {
+            return;
+        }
+        for url in event.urls() {
+            open(&handle, url.as_str());
+        }
+    });
+}
+
+/// Shows the linked page in the tenant's window, switching tenants if needed.
+///
+/// Rejected links are reported to the user.
+pub fn open(app: &AppHandle, link: &str) {
+    if let Err(e) = try_open(app, link) {
+        warn!("Rejected link {}: {}", link, e);
+        app.dialog()
+            .message(format!("SmartOps cannot open this link.\n\n{}", e))
+            .title("Cannot open link")
+            .kind(MessageDialogKind::Error)
+            .show(|_| {});
+    }
+}
+
+fn try_open(app: &AppHandle, link: &str) -> Result<(), String> {
+    let link = parse(link)?;
+    let app_url = {
+        let state = app.state::<ConfigState>();
+        let loaded = state.0.lock().map_err(|e| e.to_string())?;
+        let tenant = loaded
+            .config
+            .tenants
+            .get(&link.tenant_id)
+            .ok_or_else(|| format!("Tenant \"{}\" is not configured.", link.tenant_id))?;
+        Url::parse(&tenant.app_url).map_err(|e| e.to_string())?
+    };
+
+    let label = windows::label(&link.tenant_id);
+    if app.get_webview_window(&label).is_none() {
+        tenant::switch(app, &link.tenant_id)?;
+    }
+    let window = app
+        .get_webview_window(&label)
+        .ok_or_else(|| format!("No window for tenant {}", link.tenant_id))?;
+
+    let target = target(&app_url, &link);
+    info!("Opening link to {} in tenant {}", target, link.tenant_id);
+    connectivity::load(app, &label, target)?;
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tenant_path_query_and_fragment() {
+        let link = parse("smartops://acme-prod/catalog/default/component/foo?tab=docs#usage").unwrap();
+
+        assert_eq!(
+            link,
+            DeepLink {
+                tenant_id: "acme-prod".to_string(),
+                path: "/catalog/default/component/foo".to_string(),
+                query: Some("tab=docs".to_string()),
+                fragment: Some("usage".to_string()),
+            }
+        );
+        assert_eq!(parse("smartops://acme").unwrap().path, "/");
+        assert_eq!(parse("smartops://Acme_EU/").unwrap().tenant_id, "Acme_EU");
+    }
+
+    #[test]
+    fn rejects_foreign_and_malformed_links() {
+        assert!(parse("https://acme/catalog").is_err());
+        assert!(parse("smartops:acme/catalog").is_err());
+        assert!(parse("smartops:///catalog").is_err());
+        assert!(parse("smartops://user:pw@acme/catalog").is_err());
+        assert!(parse("smartops://acme:8080/catalog").is_err());
+        assert!(parse("not a link").is_err());
+    }
+
+    #[test]
+    fn rejects_paths_that_climb_out() {
+        assert!(parse("smartops://acme/catalog/..%2f..%2fadmin").is_err());
+        assert!(parse("smartops://acme/catalog\\..\\admin").is_err());
+        assert!(parse("smartops://acme/catalog/%5c..").is_err());
+        // Dot segments, even encoded ones, are resolved by the URL parser and stay below the root.
+        assert_eq!(parse("smartops://acme/a/../../b").unwrap().path, "/b");
+        assert_eq!(parse("smartops://acme/catalog/%2e%2e/%2E%2E/admin").unwrap().path, "/admin");
+    }
+
+    #[test]
+    fn targets_stay_below_the_app_url() {
+        let link = parse("smartops://acme/catalog/default/component/foo?tab=docs").unwrap();
+
+        let root = Url::parse("https://acme.example.com/").unwrap();
+        assert_eq!(
+            target(&root, &link).as_str(),
+            "https://acme.example.com/catalog/default/component/foo?tab=docs"
+        );
+
+        let nested = Url::parse("https://portal.example.com/acme/?lang=en").unwrap();
+        assert_eq!(
+            target(&nested, &link).as_str(),
+            "https://portal.example.com/acme/catalog/default/component/foo?tab=docs"
+        );
+
+        let home = parse("smartops://acme").unwrap();
+        assert_eq!(target(&nested, &home).as_str(), "https://portal.example.com/acme/");
+        let evil = parse("smartops://acme//evil.example.com/x").unwrap();
+        assert_eq!(target(&root, &evil).host_str(), Some("acme.example.com"));
+    }
+}
@@ -0,0 +1,221 @@
+use log::{error, info};
+use serde::Serialize;
+use std::fs;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State, WebviewWindowBuilder};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::config::{self, ConfigError, LayerPaths};
+use crate::pages;
+
+/// Label of the window shown when startup fails.
+pub const WINDOW_LABEL: &str = "diagnostics";
+
+/// Everything the diagnostics window needs to explain a failed startup.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupFailure {
+    pub env: String,
+    pub message: String,
+    pub error: ConfigError,
+    pub paths: LayerPaths,
+}
+
+impl StartupFailure {
+    fn new(app: &AppHandle, error: ConfigError) -> Self {
+        let env = config::current_env();
+        let paths = config::layer_paths(app, &env);
+        Self::with_paths(env, paths, error)
+    }
+
+    fn with_paths(env: String, paths: LayerPaths, error: ConfigError) -> Self {
+        Self {
+            paths,
+            message: error.to_string(),
+            env,
+            error,
+        }
+    }
+}
+
+/// The last startup failure, if the app is running in diagnostics mode.
+#[derive(Default)]
+pub struct StartupState(pub Mutex<Option<StartupFailure>>);
+
+/// True when startup failed only because no tenant is configured yet, which
+/// the first-run setup page can fix.
+fn needs_setup(error: &ConfigError) -> bool {
+    match error {
+        ConfigError::NotFound { .. } => true,
+        ConfigError::Invalid { issues } => issues.iter().any(|issue| issue.path == "$.tenants"),
+        _ => false,
+    }
+}
+
+/// The page that explains `error`, and its window title.
+fn page_for(error: &ConfigError) -> (&'static str, &'static str) {
+    if needs_setup(error) {
+        ("setup.html", "SmartOps - Set up")
+    } else {
+        ("diagnostics.html", "SmartOps - Startup problem")
+    }
+}
+
+/// Replaces the main window with the diagnostics window, or with the setup
+/// page on first run.
+pub fn show(app: &AppHandle, error: ConfigError) -> tauri::Result<()> {
+    error!("Startup failed: {}", error);
+    let (page, title) = page_for(&error);
+
+    let failure = StartupFailure::new(app, error);
+    if let Ok(mut state) = app.state::<StartupState>().0.lock() {
+        *state = Some(failure);
+    }
+
+    if let Some(window) = crate::windows::main(app) {
+        let _ = window.hide();
+    }
+
+    if let Some(window) = app.get_webview_window(WINDOW_LABEL) {
+        let _ = window.navigate(pages::page_url(page));
+        let _ = window.set_title(title);
+        let _ = window.set_focus();
+        return Ok(());
+    }
+
+    WebviewWindowBuilder::new(app, WINDOW_LABEL, pages::url(page))
+        .title(title)
+        .inner_size(560.0, 460.0)
+        .resizable(false)
+        .center()
+        .build()?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_startup_error(state: State<'_, StartupState>) -> Option<StartupFailure> {
+    state.0.lock().ok()?.clone()
+}
+
+/// Runs startup again after the config was fixed.
+///
+/// Async so the tenant window is not built on the main thread, which deadlocks on Windows.
+#[tauri::command]
+pub async fn retry_startup(app_handle: AppHandle, state: State<'_, StartupState>) -> Result<(), StartupFailure> {
+    info!("Retrying startup");
+
+    match crate::start(&app_handle) {
+        Ok(()) => {
+            if let Ok(mut state) = state.0.lock() {
+                *state = None;
+            }
+            if let Some(window) = app_handle.get_webview_window(WINDOW_LABEL) {
+                let _ = window.close();
+            }
+            Ok(())
+        }
+        Err(e) => {
+            error!("Startup failed again: {}", e);
+            let failure = StartupFailure::new(&app_handle, e);
+            if let Ok(mut state) = state.0.lock() {
+                *state = Some(failure.clone());
+            }
+            Err(failure)
+        }
+    }
+}
+
+#[tauri::command]
+pub fn open_config_folder(app_handle: AppHandle, state: State<'_, StartupState>) -> Result<(), String> {
+    let failure = state.0.lock().map_err(|e| e.to_string())?.clone();
+
+    // Prefer the folder of the file that failed, then the user override folder.
+    let folder = failure
+        .as_ref()
+        .and_then(|f| f.error.path().and_then(|p| p.parent()).map(|p| p.to_path_buf()))
+        .or_else(|| app_handle.path().app_config_dir().ok())
+        .ok_or("No config folder available")?;
+
+    fs::create_dir_all(&folder).map_err(|e| e.to_string())?;
+
+    app_handle
+        .opener()
+        .open_path(folder.to_string_lossy(), None::<&str>)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigIssue;
+    use std::path::PathBuf;
+
+    fn failure(error: ConfigError) -> (&'static str, serde_json::Value) {
+        let (page, _) = page_for(&error);
+        let paths = LayerPaths {
+            bundled: vec![PathBuf::from("/opt/smartops/config/dev.json")],
+            ..Default::default()
+        };
+        let failure = StartupFailure::with_paths("dev".to_string(), paths, error);
+        (page, serde_json::to_value(failure).unwrap())
+    }
+
+    #[test]
+    fn missing_config_opens_the_setup_page() {
+        let (page, failure) = failure(ConfigError::NotFound {
+            searched: vec![PathBuf::from("/opt/smartops/config/dev.json")],
+        });
+
+        assert_eq!(page, "setup.html");
+        assert_eq!(failure["error"]["kind"], "notFound");
+        assert_eq!(failure["env"], "dev");
+        assert_eq!(failure["paths"]["bundled"][0], "/opt/smartops/config/dev.json");
+    }
+
+    #[test]
+    fn parse_errors_show_the_file() {
+        let (page, failure) = failure(ConfigError::Parse {
+            path: PathBuf::from("/home/me/.config/smartops/dev.yaml"),
+            message: "mapping values are not allowed here".to_string(),
+        });
+
+        assert_eq!(page, "diagnostics.html");
+        assert_eq!(failure["error"]["kind"], "parse");
+        assert_eq!(failure["error"]["path"], "/home/me/.config/smartops/dev.yaml");
+        assert_eq!(
+            failure["message"],
+            "Failed to parse config file /home/me/.config/smartops/dev.yaml: mapping values are not allowed here"
+        );
+    }
+
+    #[test]
+    fn unknown_tenants_list_the_known_ones() {
+        let (page, failure) = failure(ConfigError::UnknownTenant {
+            tenant_id: "globex".to_string(),
+            known: vec!["acme".to_string(), "demo".to_string()],
+        });
+
+        assert_eq!(page, "diagnostics.html");
+        assert_eq!(failure["error"]["kind"], "unknownTenant");
+        assert_eq!(failure["error"]["tenantId"], "globex");
+        assert_eq!(failure["message"], "Tenant `globex` is not configured (known tenants: acme, demo)");
+    }
+
+    #[test]
+    fn only_a_missing_tenant_list_needs_setup() {
+        let invalid = |path: &str| ConfigError::Invalid {
+            issues: vec![ConfigIssue {
+                path: path.to_string(),
+                message: "at least one tenant must be configured".to_string(),
+            }],
+        };
+
+        assert!(needs_setup(&invalid("$.tenants")));
+        assert!(!needs_setup(&invalid("$.tenants.acme.appUrl")));
+        assert!(!needs_setup(&ConfigError::Read {
+            path: PathBuf::from("/etc/smartops/desktop/dev.json"),
+            message: "permission denied".to_string(),
+        }));
+    }
+}
@@ -0,0 +1,282 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use clap::Parser;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use crate::cli::Cli;
+use crate::config::ConfigState;
+use crate::{deep_link, diagnostics, tenant, windows};
+
+/// Held locked by the running instance for as long as it lives.
+const LOCK_FILE: &str = "smartops-desktop.lock";
+
+/// Next to the lock file: the running instance's port and handoff token.
+const PORT_FILE: &str = "smartops-desktop.port";
+
+/// How long a second launch keeps trying to reach the running instance,
+/// which may still be starting up.
+const HANDOFF_TIMEOUT: Duration = Duration::from_secs(5);
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Largest handoff message the running instance reads.
+const MAX_MESSAGE: u64 = 64 * 1024;
+
+/// What a later launch sends to the running instance.
+#[derive(Debug, Serialize, Deserialize)]
+struct Handoff {
+    token: String,
+    args: Vec<String>,
+}
+
+/// Outcome of `claim`.
+pub enum Claim {
+    /// This is the only instance.
+    Primary(Primary),
+    /// Another instance is running and has taken over this launch's arguments.
+    HandedOff,
+}
+
+/// The running instance: holds the lock and listens for later launches on loopback.
+pub struct Primary {
+    _lock: File,
+    listener: TcpListener,
+    token: String,
+}
+
+/// Directory holding the lock: the user's runtime dir (`XDG_RUNTIME_DIR`)
+/// where there is one, else the temp dir.
+fn lock_dir() -> PathBuf {
+    dirs::runtime_dir().unwrap_or_else(std::env::temp_dir)
+}
+
+/// Becomes the single running instance, or hands `args` to the one already running.
+pub fn claim(args: &[String]) -> Result<Claim, String> {
+    claim_in(&lock_dir(), args)
+}
+
+fn claim_in(dir: &Path, args: &[String]) -> Result<Claim, String> {
+    let lock = private_file(&dir.join(LOCK_FILE), false)
+        .map_err(|e| format!("cannot open {}: {}", dir.join(LOCK_FILE).display(), e))?;
+
+    match lock.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            hand_off(&dir.join(PORT_FILE), args)?;
+            return Ok(Claim::HandedOff);
+        }
+        Err(TryLockError::Error(e)) => return Err(format!("cannot lock {}: {}", LOCK_FILE, e)),
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| format!("cannot listen for other launches: {}", e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let token = random_token()?;
+
+    // The lock file itself is not written: Windows locks are mandatory, so
+    // later launches could not read it.
+    private_file(&dir.join(PORT_FILE), true)
+        .and_then(|mut file| writeln!(file, "{} {}", port, token))
+        .map_err(|e| format!("cannot write {}: {}", PORT_FILE, e))?;
+
+    Ok(Claim::Primary(Primary {
+        _lock: lock,
+        listener,
+        token,
+    }))
+}
+
+/// Opens a file only the current user can read, since the port file holds the handoff token.
+fn private_file(path: &Path, truncate: bool) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create(true).truncate(truncate);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+fn random_token() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| format!("no randomness available: {}", e))?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Sends `args` to the running instance and waits for it to confirm.
+fn hand_off(port_file: &Path, args: &[String]) -> Result<(), String> {
+    let deadline = Instant::now() + HANDOFF_TIMEOUT;
+    loop {
+        match try_hand_off(port_file, args) {
+            Ok(()) => return Ok(()),
+            Err(e) if Instant::now() >= deadline => {
+                return Err(format!("the running instance did not respond: {}", e));
+            }
+            Err(_) => thread::sleep(RETRY_INTERVAL),
+        }
+    }
+}
+
+fn try_hand_off(port_file: &Path, args: &[String]) -> Result<(), String> {
+    let contents = fs::read_to_string(port_file).map_err(|e| e.to_string())?;
+    let (port, token) = contents
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| format!("{} is incomplete", PORT_FILE))?;
+    let port: u16 = port.parse().map_err(|_| format!("{} is incomplete", PORT_FILE))?;
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(HANDOFF_TIMEOUT)).map_err(|e| e.to_string())?;
+    let handoff = Handoff {
+        token: token.to_string(),
+        args: args.to_vec(),
+    };
+    let message = serde_json::to_string(&handoff).map_err(|e| e.to_string())?;
+    writeln!(stream, "{}", message).map_err(|e| e.to_string())?;
+    stream.shutdown(Shutdown::Write).map_err(|e| e.to_string())?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).map_err(|e| e.to_string())?;
+    match reply.trim() {
+        "ok" => Ok(()),
+        _ => Err("the handoff was refused".to_string()),
+    }
+}
+
+impl Primary {
+    /// Passes the arguments of every later launch to `on_launch` on a background thread.
+    pub fn serve<F: FnMut(Vec<String>) + Send + 'static>(self, mut on_launch: F) {
+        let result = thread::Builder::new().name("single-instance".to_string()).spawn(move || {
+            let _lock = self._lock;
+            for stream in self.listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("Failed to accept a launch handoff: {}", e);
+                        continue;
+                    }
+                };
+                match receive(stream, &self.token) {
+                    Ok(args) => on_launch(args),
+                    Err(e) => warn!("Ignored a launch handoff: {}", e),
+                }
+            }
+        });
+
+        if let Err(e) = result {
+            error!("Failed to listen for other launches: {}", e);
+        }
+    }
+}
+
+fn receive(mut stream: TcpStream, token: &str) -> Result<Vec<String>, String> {
+    stream.set_read_timeout(Some(HANDOFF_TIMEOUT)).map_err(|e| e.to_string())?;
+
+    let mut line = String::new();
+    BufReader::new((&stream).take(MAX_MESSAGE))
+        .read_line(&mut line)
+        .map_err(|e| e.to_string())?;
+    let handoff: Handoff = serde_json::from_str(&line).map_err(|e| format!("invalid handoff: {}", e))?;
+    if handoff.token != token {
+        let _ = writeln!(stream, "refused");
+        return Err("wrong token".to_string());
+    }
+
+    writeln!(stream, "ok").map_err(|e| e.to_string())?;
+    Ok(handoff.args)
+}
+
+/// Acts on a later launch: opens its link or tenant and brings the app to the front.
+pub fn on_launch(app: &AppHandle, args: Vec<String>) {
+    info!("Another launch handed over with {:?}", args.get(1..).unwrap_or_default());
+    let cli = match Cli::try_parse_from(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            warn!("Ignoring the arguments of another launch: {}", e);
+            Cli::default()
+        }
+    };
+
+    // Until startup succeeds there is only the diagnostics or setup window.
+    if app.try_state::<ConfigState>().is_some() {
+        if let Some(link) = &cli.link {
+            deep_link::open(app, link);
+            return;
+        }
+        if let Some(tenant_id) = &cli.tenant {
+            if let Err(e) = tenant::switch(app, tenant_id) {
+                warn!("Cannot switch to tenant {} for another launch: {}", tenant_id, e);
+            }
+        }
+    }
+    if cli.hidden {
+        return;
+    }
+
+    let window = windows::target(app).or_else(|| app.get_webview_window(diagnostics::WINDOW_LABEL));
+    if let Some(window) = window {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("smartops-instance-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn second_launch_hands_its_arguments_over() {
+        let dir = temp_dir("handoff");
+        let Ok(Claim::Primary(primary)) = claim_in(&dir, &args(&["smartops-desktop"])) else {
+            panic!("the first launch should be the primary instance");
+        };
+        let (launched, received) = mpsc::channel();
+        primary.serve(move |args| launched.send(args).unwrap());
+
+        let second = args(&["smartops-desktop", "smartops://acme/catalog"]);
+        assert!(matches!(claim_in(&dir, &second), Ok(Claim::HandedOff)));
+        assert_eq!(received.recv_timeout(Duration::from_secs(2)).unwrap(), second);
+    }
+
+    #[test]
+    fn handoffs_need_the_token() {
+        let dir = temp_dir("token");
+        let Ok(Claim::Primary(primary)) = claim_in(&dir, &[]) else {
+            panic!("the first launch should be the primary instance");
+        };
+        let port = primary.listener.local_addr().unwrap().port();
+        let (launched, received) = mpsc::channel();
+        primary.serve(move |args| launched.send(args).unwrap());
+
+        fs::write(dir.join("forged.port"), format!("{} guessed\n", port)).unwrap();
+        assert!(try_hand_off(&dir.join("forged.port"), &args(&["smartops-desktop"])).is_err());
+        assert!(received.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    #[test]
+    fn a_stale_port_file_does_not_block_startup() {
+        let dir = temp_dir("stale");
+        fs::write(dir.join(PORT_FILE), "1 left-by-a-crash\n").unwrap();
+
+        assert!(matches!(claim_in(&dir, &[]), Ok(Claim::Primary(_))));
+        assert_ne!(fs::read_to_string(dir.join(PORT_FILE)).unwrap(), "1 left-by-a-crash\n");
+    }
+}
@@ -1,73 +1,42 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use log::{error, info};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs;
+mod auth;
+mod claims;
+mod cli;
+pub mod config;
+mod connectivity;
+mod deep_link;
+mod diagnostics;
+mod instance;
+mod pages;
+mod probe;
+mod secure_store;
+mod tenant;
+mod windows;
+
+use config::{ConfigError, ConfigState};
+use diagnostics::StartupState;
+use tenant::ActiveTenant;
+use log::{error, info, warn};
 use std::path::PathBuf;
 use std::sync::Mutex;
 use tauri::{
     image::Image,
-    menu::{Menu, MenuItem},
+    menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    AppHandle, Emitter, Manager, State, WindowEvent,
+    AppHandle, Emitter, Manager, State, Wry,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TenantConfig {
-    pub app_url: String,
-    #[serde(rename = "name")]
-    pub name: Option<String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DesktopConfig {
-    pub env: String,
-    pub default_tenant: String,
-    pub tenants: HashMap<String, TenantConfig>,
-    pub keycloak: Option<KeycloakConfig>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct KeycloakConfig {
-    #[serde(rename = "tenantClaim")]
-    pub tenant_claim: Option<String>,
-}
-
-#[derive(Default)]
-pub struct SecureStore(Mutex<HashMap<String, String>>);
-
-fn get_config_path(env: &str) -> PathBuf {
-    let base = std::env::current_exe()
-        .ok()
-        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-        .unwrap_or_else(|| PathBuf::from("."));
-    
-    base.join("config").join(format!("{}.json", env))
-}
-
-fn load_config(env: &str) -> Result<DesktopConfig, String> {
-    let config_path = get_config_path(env);
-    info!("Loading config from: {:?}", config_path);
-    
-    let raw = fs::read_to_string(&config_path)
-        .map_err(|e| format!("Failed to read config file: {}", e))?;
-    
-    serde_json::from_str(&raw)
-        .map_err(|e| format!("Failed to parse config: {}", e))
-}
+pub use config::{DesktopConfig, KeycloakConfig, TenantConfig};
+pub use secure_store::SecureStore;
 
 #[tauri::command]
-fn get_config() -> Result<serde_json::Value, String> {
-    let env = std::env::var("DESKTOP_ENV").unwrap_or_else(|_| "dev".to_string());
-    let config = load_config(&env)?;
+fn get_config(state: State<'_, ConfigState>, active: State<'_, ActiveTenant>) -> Result<serde_json::Value, String> {
+    let loaded = state.0.lock().map_err(|e| e.to_string())?;
+    let config = &loaded.config;
     
-    let tenant_id = std::env::var("DESKTOP_TENANT")
-        .unwrap_or_else(|_| config.default_tenant.clone());
-    
-    let tenant = config.tenants.get(&tenant_id)
-        .or_else(|| config.tenants.values().next())
-        .ok_or("No tenant configuration found")?;
+    let tenant_id = active.0.lock().map_err(|e| e.to_string())?.clone();
+    let tenant = config.tenant(&tenant_id)?;
     
     Ok(serde_json::json!({
         "env": config.env,
@@ -78,29 +47,29 @@ fn get_config() -> Result<serde_json::Value, String> {
 }
 
 #[tauri::command]
-fn notify(title: String, body: String, app_handle: AppHandle) -> Result<(), String> {
-    app_handle.emit("notification", serde_json::json!({ "title": title, "body": body }))
-        .map_err(|e| e.to_string())
-}
-
-#[tauri::command]
-fn get_secure_store(key: String, store: State<'_, SecureStore>) -> Option<String> {
-    let store = store.0.lock().ok()?;
-    store.get(&key).cloned()
+fn get_config_sources(state: State<'_, ConfigState>) -> Result<serde_json::Value, String> {
+    let loaded = state.0.lock().map_err(|e| e.to_string())?;
+    
+    Ok(serde_json::json!({
+        "files": loaded.files,
+        "origins": loaded.origins,
+        "remote": loaded.remote,
+    }))
 }
 
+/// Async so the layered load and the remote catalogue fetch stay off the main thread.
 #[tauri::command]
-fn set_secure_store(key: String, value: String, store: State<'_, SecureStore>) -> Result<(), String> {
-    let mut store = store.0.lock().map_err(|e| e.to_string())?;
-    store.insert(key, value);
-    Ok(())
+async fn reload_config(app_handle: AppHandle) -> Result<Vec<config::ConfigChange>, String> {
+    tauri::async_runtime::spawn_blocking(move || config::watch::reload(&app_handle))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(String::from)
 }
 
 #[tauri::command]
-fn delete_secure_store(key: String, store: State<'_, SecureStore>) -> Result<(), String> {
-    let mut store = store.0.lock().map_err(|e| e.to_string())?;
-    store.remove(&key);
-    Ok(())
+fn notify(title: String, body: String, app_handle: AppHandle) -> Result<(), String> {
+    app_handle.emit("notification", serde_json::json!({ "title": title, "body": body }))
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -128,13 +97,73 @@ fn set_auto_launch(enabled: bool, app_handle: AppHandle) -> Result<(), String> {
     Ok(())
 }
 
-fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+fn build_tray_menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
     let show = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
     let hide = MenuItem::with_id(app, "hide", "Hide", true, None::<&str>)?;
     let reload = MenuItem::with_id(app, "reload", "Reload", true, None::<&str>)?;
+    let logout_all = MenuItem::with_id(app, "logout-all", "Sign Out of All Tenants", true, None::<&str>)?;
     let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let tenants = build_tenants_menu(app, "Tenants", tenant::MENU_ID_PREFIX, true)?;
+    let open_tenant = build_tenants_menu(app, "Open in New Window", windows::OPEN_MENU_ID_PREFIX, false)?;
+    let open_windows = build_windows_menu(app)?;
+    let top = PredefinedMenuItem::separator(app)?;
+    let bottom = PredefinedMenuItem::separator(app)?;
     
-    let menu = Menu::with_items(app, &[&show, &hide, &reload, &quit])?;
+    Menu::with_items(
+        app,
+        &[&show, &hide, &reload, &top, &tenants, &open_tenant, &open_windows, &bottom, &logout_all, &quit],
+    )
+}
+
+/// Lists the configured tenants under `id_prefix`, optionally with a check mark on the active one.
+fn build_tenants_menu(app: &AppHandle, title: &str, id_prefix: &str, check_active: bool) -> tauri::Result<Submenu<Wry>> {
+    let menu = Submenu::new(app, title, true)?;
+    let Some(state) = app.try_state::<ConfigState>() else { return Ok(menu) };
+    let Ok(loaded) = state.0.lock() else { return Ok(menu) };
+    let active = tenant::active_id(app);
+    
+    let mut tenants: Vec<_> = loaded.config.tenants.iter().collect();
+    tenants.sort_by_key(|(id, _)| id.as_str());
+    for (id, tenant) in tenants {
+        let id_with_prefix = format!("{}{}", id_prefix, id);
+        let text = tenant.name.as_deref().unwrap_or(id);
+        if check_active {
+            let checked = active.as_deref() == Some(id.as_str());
+            menu.append(&CheckMenuItem::with_id(app, id_with_prefix, text, true, checked, None::<&str>)?)?;
+        } else {
+            menu.append(&MenuItem::with_id(app, id_with_prefix, text, true, None::<&str>)?)?;
+        }
+    }
+    
+    Ok(menu)
+}
+
+/// Lists the open tenant windows so one can be brought to the front.
+fn build_windows_menu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+    let windows = windows::tenant_windows(app);
+    let menu = Submenu::new(app, "Windows", !windows.is_empty())?;
+    
+    for window in windows {
+        let id = format!("{}{}", windows::WINDOW_MENU_ID_PREFIX, window.label());
+        let title = window.title().unwrap_or_else(|_| window.label().to_string());
+        menu.append(&MenuItem::with_id(app, id, title, true, None::<&str>)?)?;
+    }
+    
+    Ok(menu)
+}
+
+/// Rebuilds the tray menu, e.g. after the config changed.
+pub(crate) fn refresh_tray(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id("main-tray") else { return };
+    
+    let result = build_tray_menu(app).and_then(|menu| tray.set_menu(Some(menu)));
+    if let Err(e) = result {
+        error!("Failed to refresh tray menu: {}", e);
+    }
+}
+
+fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    let menu = build_tray_menu(app)?;
     
     let icon_bytes = include_bytes!("../icons/icon.png");
     let icon = Image::from_bytes(icon_bytes)?;
@@ -146,31 +175,45 @@ fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
         .on_menu_event(move |app, event| {
             match event.id.as_ref() {
                 "show" => {
-                    if let Some(window) = app.get_webview_window("main") {
+                    if let Some(window) = windows::target(app) {
                         let _ = window.show();
                         let _ = window.set_focus();
                     }
                 }
                 "hide" => {
-                    if let Some(window) = app.get_webview_window("main") {
+                    if let Some(window) = windows::target(app) {
                         let _ = window.hide();
                     }
                 }
                 "reload" => {
-                    if let Some(window) = app.get_webview_window("main") {
+                    if let Some(window) = windows::target(app) {
                         let _ = window.reload();
                     }
                 }
+                "logout-all" => {
+                    auth::logout_all(app);
+                }
                 "quit" => {
                     app.exit(0);
                 }
-                _ => {}
+                id => {
+                    if let Some(tenant_id) = id.strip_prefix(tenant::MENU_ID_PREFIX) {
+                        tenant::switch_from_menu(app, tenant_id);
+                    } else if let Some(tenant_id) = id.strip_prefix(windows::OPEN_MENU_ID_PREFIX) {
+                        windows::open_from_menu(app, tenant_id);
+                    } else if let Some(label) = id.strip_prefix(windows::WINDOW_MENU_ID_PREFIX) {
+                        if let Some(window) = app.get_webview_window(label) {
+                            let _ = window.show();
+                            let _ = window.set_focus();
+                        }
+                    }
+                }
             }
         })
         .on_tray_icon_event(|tray, event| {
             if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
                 let app = tray.app_handle();
-                if let Some(window) = app.get_webview_window("main") {
+                if let Some(window) = windows::target(app) {
                     if window.is_visible().unwrap_or(false) {
                         let _ = window.hide();
                     } else {
@@ -185,10 +228,69 @@ fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Loads the config and brings up the tray and main window.
+///
+/// Nothing is registered until the config and tenant are known to be good, so
+/// this can be retried from the diagnostics window after a failure.
+pub(crate) fn start(app: &AppHandle) -> Result<(), ConfigError> {
+    let env = config::current_env();
+    let loaded = config::load(app, &env)?;
+    
+    let tenant_id = tenant::initial_id(app, &loaded.config);
+    let tenant = loaded.config.tenant(&tenant_id)?.clone();
+    
+    info!("Loading app URL: {}", tenant.app_url);
+    let watched: Vec<PathBuf> = config::layer_paths(app, &env).all().cloned().collect();
+    app.manage(ConfigState(Mutex::new(loaded)));
+    app.manage(ActiveTenant(Mutex::new(tenant_id.clone())));
+    config::watch::spawn(app.clone(), watched);
+    
+    if let Err(e) = setup_tray(app) {
+        error!("Failed to setup tray: {}", e);
+    }
+    
+    match windows::open(app, &tenant_id, &tenant) {
+        Ok(window) => {
+            let args = cli::args();
+            if !args.hidden {
+                let _ = window.show();
+            }
+            if args.minimized {
+                let _ = window.minimize();
+            }
+        }
+        Err(e) => error!("Failed to open window for tenant {}: {}", tenant_id, e),
+    }
+    deep_link::open_startup_link(app);
+    secure_store::import_legacy(app, &tenant_id);
+    
+    Ok(())
+}
+
+/// Prints the resolved config for `--print-config` and exits.
+fn print_config(app: &AppHandle) {
+    let code = match config::load(app, &config::current_env()) {
+        Ok(loaded) => {
+            println!("{}", serde_json::to_string_pretty(&loaded).unwrap_or_default());
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    };
+    app.exit(code);
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-        .init();
+    let args = cli::init();
+    
+    let mut logger = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    if let Some(level) = &args.log_level {
+        logger.parse_filters(level);
+    }
+    logger.init();
     
     info!("Starting SmartOps Desktop");
     
@@ -196,17 +298,19 @@ pub fn run() {
         error!("Application panic: {}", panic_info);
     }));
     
-    let config = load_config(&std::env::var("DESKTOP_ENV").unwrap_or_else(|_| "dev".to_string()))
-        .expect("Failed to load config");
-    
-    let tenant_id = std::env::var("DESKTOP_TENANT")
-        .unwrap_or_else(|_| config.default_tenant.clone());
-    
-    let tenant = config.tenants.get(&tenant_id)
-        .or_else(|| config.tenants.values().next())
-        .expect("No tenant configuration found");
-    
-    info!("Loading app URL: {}", tenant.app_url);
+    // `--print-config` only reads, so it may run next to the app.
+    let mut primary = None;
+    if !args.exits_early() {
+        let argv: Vec<String> = std::env::args().collect();
+        match instance::claim(&argv) {
+            Ok(instance::Claim::Primary(claimed)) => primary = Some(claimed),
+            Ok(instance::Claim::HandedOff) => {
+                info!("SmartOps Desktop is already running; handed this launch over to it");
+                return;
+            }
+            Err(e) => warn!("Single-instance check failed, starting anyway: {}", e),
+        }
+    }
     
     tauri::Builder::default()
         .plugin(tauri_plugin_notification::init())
@@ -219,37 +323,70 @@ pub fn run() {
         ))
         .plugin(tauri_plugin_store::Builder::default().build())
         .plugin(tauri_plugin_shell::init())
-        .manage(SecureStore::default())
-        .setup(|app| {
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_deep_link::init())
+        .register_uri_scheme_protocol(pages::SCHEME, |_ctx, request| pages::handle(&request))
+        .manage(StartupState::default())
+        .manage(secure_store::Migration::default())
+        .manage(windows::LastFocused::default())
+        .manage(connectivity::Connectivity::default())
+        .manage(auth::RefreshTasks::default())
+        .manage(auth::KeyCache::default())
+        .setup(move |app| {
             info!("Setting up application");
             
-            if let Err(e) = setup_tray(app.handle()) {
-                error!("Failed to setup tray: {}", e);
+            if args.exits_early() {
+                print_config(app.handle());
+                return Ok(());
             }
             
-            if let Some(window) = app.get_webview_window("main") {
-                let window_clone = window.clone();
-                window.on_window_event(move |event| {
-                    if let WindowEvent::CloseRequested { api, .. } = event {
-                        #[cfg(target_os = "macos")]
-                        {
-                            api.prevent_close();
-                            let _ = window_clone.hide();
-                        }
-                    }
-                });
+            app.manage(SecureStore::open(app.handle()));
+            secure_store::spawn_sweeper(app.handle());
+            if let Some(primary) = primary {
+                let handle = app.handle().clone();
+                primary.serve(move |argv| instance::on_launch(&handle, argv));
+            }
+            deep_link::listen(app.handle());
+            if let Err(e) = start(app.handle()) {
+                diagnostics::show(app.handle(), e)?;
             }
             
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_config,
+            get_config_sources,
+            reload_config,
             notify,
-            get_secure_store,
-            set_secure_store,
-            delete_secure_store,
+            secure_store::get_secure_store,
+            secure_store::set_secure_store,
+            secure_store::delete_secure_store,
+            secure_store::list_secure_keys,
+            secure_store::clear_secure_store,
+            secure_store::list_all_secure_keys,
+            secure_store::get_secure_store_status,
+            secure_store::get_secure_store_migration,
+            secure_store::unlock_secure_store,
+            secure_store::set_secure_store_passphrase,
             get_auto_launch,
             set_auto_launch,
+            tenant::switch_tenant,
+            tenant::select_tenant_from_token,
+            auth::login,
+            auth::get_session,
+            auth::get_identity,
+            auth::logout,
+            tenant::list_tenants,
+            tenant::add_tenant,
+            tenant::update_tenant,
+            tenant::remove_tenant,
+            windows::open_tenant_window,
+            connectivity::get_connectivity,
+            connectivity::retry_connection,
+            windows::wipe_tenant_data,
+            diagnostics::get_startup_error,
+            diagnostics::retry_startup,
+            diagnostics::open_config_folder,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
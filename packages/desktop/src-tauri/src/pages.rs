@@ -0,0 +1,52 @@
+use tauri::http::{header::CONTENT_TYPE, Request, Response, StatusCode};
+use tauri::{Url, WebviewUrl};
+
+/// Scheme of the webview protocol serving the shell's own pages.
+pub const SCHEME: &str = "desktop";
+
+const PAGES: &[(&str, &str)] = &[
+    ("diagnostics.html", include_str!("../pages/diagnostics.html")),
+    ("offline.html", include_str!("../pages/offline.html")),
+    ("setup.html", include_str!("../pages/setup.html")),
+];
+
+fn base_url() -> Url {
+    // Custom protocols are exposed as `http://<scheme>.localhost` on Windows.
+    #[cfg(windows)]
+    let base = format!("http://{}.localhost/", SCHEME);
+    #[cfg(not(windows))]
+    let base = format!("{}://localhost/", SCHEME);
+
+    Url::parse(&base).expect("bundled page URLs are valid")
+}
+
+/// Returns the address of one of the bundled pages.
+pub fn page_url(page: &str) -> Url {
+    base_url().join(page).expect("bundled page URLs are valid")
+}
+
+/// Returns the URL a webview uses to load one of the bundled pages.
+pub fn url(page: &str) -> WebviewUrl {
+    WebviewUrl::CustomProtocol(page_url(page))
+}
+
+/// Returns true if `url` points at one of the bundled pages.
+pub fn is_page(url: &Url) -> bool {
+    url.origin() == base_url().origin()
+}
+
+/// Serves a bundled page for the `desktop` protocol.
+pub fn handle(request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let page = request.uri().path().trim_start_matches('/');
+
+    let response = match PAGES.iter().find(|(name, _)| *name == page) {
+        Some((_, html)) => Response::builder()
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(html.as_bytes().to_vec()),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Vec::new()),
+    };
+
+    response.unwrap_or_default()
+}
@@ -0,0 +1,148 @@
+use serde::Serialize;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use url::Url;
+
+/// Step of a reachability check, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeStage {
+    Dns,
+    Tcp,
+    Tls,
+    Http,
+}
+
+/// The first stage that failed and why.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProbeFailure {
+    pub stage: ProbeStage,
+    pub message: String,
+}
+
+impl ProbeFailure {
+    fn new(stage: ProbeStage, message: impl Into<String>) -> Self {
+        Self {
+            stage,
+            message: message.into(),
+        }
+    }
+}
+
+/// Checks that `url` resolves, accepts connections, completes a TLS handshake
+/// (for `https`) and answers HTTP without a server error.
+///
+/// Client errors such as 401 or 404 count as reachable: the server is up, and
+/// the webview will show whatever it answers.
+pub fn probe(url: &Url, timeout: Duration) -> Result<(), ProbeFailure> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| ProbeFailure::new(ProbeStage::Dns, "URL has no host"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| ProbeFailure::new(ProbeStage::Tcp, "URL has no port"))?;
+
+    let addrs: Vec<SocketAddr> = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| ProbeFailure::new(ProbeStage::Dns, format!("cannot resolve {}: {}", host, e)))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(ProbeFailure::new(ProbeStage::Dns, format!("{} has no addresses", host)));
+    }
+
+    let mut last_error = None;
+    let connected = addrs.iter().any(|addr| match TcpStream::connect_timeout(addr, timeout) {
+        Ok(_) => true,
+        Err(e) => {
+            last_error = Some(e);
+            false
+        }
+    });
+    if !connected {
+        let reason = last_error.map(|e| e.to_string()).unwrap_or_default();
+        return Err(ProbeFailure::new(
+            ProbeStage::Tcp,
+            format!("cannot connect to {}:{}: {}", host, port, reason),
+        ));
+    }
+
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+    match agent.get(url.as_str()).call() {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(status, _)) if status < 500 => Ok(()),
+        Err(ureq::Error::Status(status, response)) => Err(ProbeFailure::new(
+            ProbeStage::Http,
+            format!("server answered {} {}", status, response.status_text()),
+        )),
+        Err(ureq::Error::Transport(transport)) => {
+            // DNS and TCP already succeeded, so a failed connection on https is the handshake.
+            let stage = match transport.kind() {
+                ureq::ErrorKind::Dns => ProbeStage::Dns,
+                ureq::ErrorKind::ConnectionFailed if url.scheme() == "https" => ProbeStage::Tls,
+                ureq::ErrorKind::ConnectionFailed => ProbeStage::Tcp,
+                _ => ProbeStage::Http,
+            };
+            Err(ProbeFailure::new(stage, transport.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// Accepts the TCP check, answers the HTTP request with `status` and returns the URL to hit.
+    fn serve(status: &'static str) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/healthz", listener.local_addr().unwrap())).unwrap();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                // The TCP stage connects and closes without sending anything.
+                if BufReader::new(&stream).read_line(&mut request_line).unwrap_or(0) == 0 {
+                    continue;
+                }
+                write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+            }
+        });
+
+        url
+    }
+
+    #[test]
+    fn healthy_and_client_error_responses_are_reachable() {
+        assert_eq!(probe(&serve("200 OK"), TIMEOUT), Ok(()));
+        assert_eq!(probe(&serve("401 Unauthorized"), TIMEOUT), Ok(()));
+    }
+
+    #[test]
+    fn server_errors_fail_the_http_stage() {
+        let failure = probe(&serve("503 Service Unavailable"), TIMEOUT).unwrap_err();
+
+        assert_eq!(failure.stage, ProbeStage::Http);
+        assert!(failure.message.contains("503"), "{}", failure.message);
+    }
+
+    #[test]
+    fn refused_connections_fail_the_tcp_stage() {
+        // Bind and drop a listener to get a port nothing listens on.
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let url = Url::parse(&format!("http://127.0.0.1:{}/", port)).unwrap();
+
+        assert_eq!(probe(&url, TIMEOUT).unwrap_err().stage, ProbeStage::Tcp);
+    }
+
+    #[test]
+    fn unknown_hosts_fail_the_dns_stage() {
+        let url = Url::parse("https://smartops-probe.invalid/").unwrap();
+
+        assert_eq!(probe(&url, TIMEOUT).unwrap_err().stage, ProbeStage::Dns);
+    }
+}
@@ -0,0 +1,211 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use super::key::{self, KeySource};
+
+/// Layout version of the file; bumped when the envelope or plaintext change.
+///
+/// Version 1 held bare values; they are read as entries without metadata.
+const VERSION: u32 = 2;
+
+/// Binds the ciphertext to this file format, so it cannot be passed off as something else.
+///
+/// Deliberately frozen at "v1" and not tied to `VERSION`: files of every
+/// version are sealed with it, and the version itself is checked from the
+/// envelope. Changing it would make every existing store unreadable.
+const ASSOCIATED_DATA: &[u8] = b"smartops-desktop secure store v1";
+
+/// On-disk form of the store: the entries as JSON, encrypted with XChaCha20-Poly1305.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    version: u32,
+    key: KeySource,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// A stored secret and what is known about it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    pub value: String,
+    /// What the secret is, for people looking at the store's keys.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Unix timestamp after which the entry is gone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl Entry {
+    pub fn new(value: String) -> Self {
+        Self {
+            value,
+            label: None,
+            expires_at: None,
+        }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+/// The store's contents: entries by key.
+pub type Entries = BTreeMap<String, Entry>;
+
+/// Why a store file could not be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file is damaged or its key is lost; it will never load again.
+    Corrupt(String),
+    /// Its key cannot be read right now; the file is left alone.
+    Unavailable(String),
+}
+
+/// The encrypted file backing the store.
+pub struct StoreFile {
+    path: PathBuf,
+    key_source: KeySource,
+    cipher: XChaCha20Poly1305,
+}
+
+impl StoreFile {
+    /// Starts a new, empty store at `path` with a new key, kept in `preferred` if possible.
+    pub fn create(path: &Path, preferred: KeySource) -> Result<Self, String> {
+        let (key_source, key) = key::create(parent(path), preferred)?;
+        let file = Self::new(path, key_source, &key);
+        file.save(&Entries::new())?;
+        Ok(file)
+    }
+
+    /// Opens the store at `path`, or returns `None` if there is none yet.
+    pub fn open(path: &Path) -> Result<Option<(Self, Entries)>, LoadError> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(LoadError::Unavailable(e.to_string())),
+        };
+        let corrupt = |reason: &str| LoadError::Corrupt(reason.to_string());
+
+        let envelope: Envelope = serde_json::from_slice(&bytes).map_err(|_| corrupt("it is not a secure store"))?;
+        if !(1..=VERSION).contains(&envelope.version) {
+            return Err(corrupt(&format!("it has unknown version {}", envelope.version)));
+        }
+        let key = key::load(envelope.key, parent(path))?;
+        let file = Self::new(path, envelope.key, &key);
+
+        let nonce = STANDARD.decode(&envelope.nonce).map_err(|_| corrupt("its nonce is malformed"))?;
+        if nonce.len() != 24 {
+            return Err(corrupt("its nonce is malformed"));
+        }
+        let ciphertext = STANDARD.decode(&envelope.ciphertext).map_err(|_| corrupt("its ciphertext is malformed"))?;
+        let plaintext = file
+            .cipher
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: ASSOCIATED_DATA,
+                },
+            )
+            .map_err(|_| corrupt("it fails its integrity check"))?;
+        let entries = match envelope.version {
+            1 => serde_json::from_slice::<BTreeMap<String, String>>(&plaintext)
+                .map(|values| values.into_iter().map(|(key, value)| (key, Entry::new(value))).collect()),
+            _ => serde_json::from_slice(&plaintext),
+        };
+        let entries = entries.map_err(|_| corrupt("its entries are malformed"))?;
+
+        Ok(Some((file, entries)))
+    }
+
+    fn new(path: &Path, key_source: KeySource, key: &Key) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            key_source,
+            cipher: XChaCha20Poly1305::new(key),
+        }
+    }
+
+    /// Encrypts `entries` under a fresh nonce and replaces the file with them.
+    pub fn save(&self, entries: &Entries) -> Result<(), String> {
+        let plaintext = serde_json::to_vec(entries).map_err(|e| e.to_string())?;
+        self.write(VERSION, &plaintext)
+    }
+
+    fn write(&self, version: u32, plaintext: &[u8]) -> Result<(), String> {
+        let mut nonce = [0u8; 24];
+        getrandom::getrandom(&mut nonce).map_err(|e| format!("no randomness available: {}", e))?;
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: ASSOCIATED_DATA,
+                },
+            )
+            .map_err(|e| e.to_string())?;
+
+        let envelope = Envelope {
+            version,
+            key: self.key_source,
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+        let json = serde_json::to_vec(&envelope).map_err(|e| e.to_string())?;
+        write_private(&self.path, &json).map_err(|e| format!("cannot write {}: {}", self.path.display(), e))
+    }
+}
+
+fn parent(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new("."))
+}
+
+/// Replaces `path` with `contents` in one step, readable only by the user.
+///
+/// The contents go to a temporary file that is flushed to disk and renamed
+/// over `path`, so a crash leaves either the old or the new file.
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    fs::create_dir_all(parent(path))?;
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp = PathBuf::from(temp_name);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_1_values_become_entries() {
+        let dir = std::env::temp_dir().join(format!("smartops-secure-store-v1-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("secure-store.dat");
+        let file = StoreFile::create(&path, KeySource::File).unwrap();
+        file.write(1, br#"{"token":"s3cret"}"#).unwrap();
+
+        let (_, entries) = StoreFile::open(&path).ok().flatten().unwrap();
+        assert_eq!(entries.get("token"), Some(&Entry::new("s3cret".to_string())));
+    }
+}
@@ -0,0 +1,111 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::Key;
+use hkdf::Hkdf;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use super::file::{write_private, LoadError};
+
+/// Keyring entry holding the store's secret: service and account name.
+const KEYRING_SERVICE: &str = "smartops-desktop";
+const KEYRING_USER: &str = "secure-store";
+
+/// File next to the store holding its secret where there is no keyring.
+const KEY_FILE: &str = "secure-store.key";
+
+/// Set to `file` to keep the secret in `KEY_FILE` even if a keyring is available.
+const BACKEND_VAR: &str = "DESKTOP_SECURE_STORE";
+
+const SECRET_LEN: usize = 32;
+
+/// Where the secret the store's key is derived from is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KeySource {
+    /// The OS keyring: Keychain, Credential Manager or the Secret Service.
+    Keyring,
+    /// `secure-store.key`, readable only by the user.
+    File,
+}
+
+/// Where new secrets go: the keyring, unless `DESKTOP_SECURE_STORE=file`.
+pub fn preferred() -> KeySource {
+    match std::env::var(BACKEND_VAR).as_deref() {
+        Ok("file") => KeySource::File,
+        _ => KeySource::Keyring,
+    }
+}
+
+/// Creates a new secret in `preferred`, falling back to `KEY_FILE` if there is
+/// no keyring, and returns where it went and its key.
+pub fn create(dir: &Path, preferred: KeySource) -> Result<(KeySource, Key), String> {
+    let mut secret = [0u8; SECRET_LEN];
+    getrandom::getrandom(&mut secret).map_err(|e| format!("no randomness available: {}", e))?;
+
+    if preferred == KeySource::Keyring {
+        match store_in_keyring(&secret) {
+            Ok(()) => return Ok((KeySource::Keyring, derive(&secret))),
+            Err(e) => warn!("No OS keyring for the secure store, keeping its key in {}: {}", KEY_FILE, e),
+        }
+    }
+
+    write_private(&dir.join(KEY_FILE), &secret).map_err(|e| format!("cannot write {}: {}", KEY_FILE, e))?;
+    Ok((KeySource::File, derive(&secret)))
+}
+
+/// The key of an existing store.
+///
+/// A secret that is gone makes the store unreadable for good; one that cannot
+/// be read right now (e.g. a locked keyring) may be readable on the next start.
+pub fn load(source: KeySource, dir: &Path) -> Result<Key, LoadError> {
+    let secret = match source {
+        KeySource::Keyring => {
+            let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(unavailable)?;
+            match entry.get_password() {
+                Ok(encoded) => STANDARD.decode(encoded).map_err(|e| LoadError::Corrupt(e.to_string()))?,
+                Err(keyring::Error::NoEntry) => return Err(LoadError::Corrupt("its key is gone from the keyring".to_string())),
+                Err(e) => return Err(unavailable(e)),
+            }
+        }
+        KeySource::File => match fs::read(dir.join(KEY_FILE)) {
+            Ok(secret) => secret,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(LoadError::Corrupt(format!("its key file {} is gone", KEY_FILE)));
+            }
+            Err(e) => return Err(unavailable(e)),
+        },
+    };
+
+    if secret.len() != SECRET_LEN {
+        return Err(LoadError::Corrupt("its key is malformed".to_string()));
+    }
+    Ok(derive(&secret))
+}
+
+fn store_in_keyring(secret: &[u8]) -> keyring::Result<()> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?;
+    entry.set_password(&STANDARD.encode(secret))?;
+    // Some backends accept writes they cannot keep; only trust what reads back.
+    match entry.get_password()? == STANDARD.encode(secret) {
+        true => Ok(()),
+        false => Err(keyring::Error::Invalid("secret".to_string(), "did not read back".to_string())),
+    }
+}
+
+fn unavailable(error: impl std::fmt::Display) -> LoadError {
+    LoadError::Unavailable(format!("cannot read its key: {}", error))
+}
+
+/// The encryption key for a secret, so the stored secret is never used as a key directly.
+fn derive(secret: &[u8]) -> Key {
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(None, secret)
+        .expand(b"smartops-desktop secure store v1", &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
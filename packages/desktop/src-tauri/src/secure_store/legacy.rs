@@ -0,0 +1,272 @@
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockDecryptMut, KeyIvInit};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::{info, warn};
+use serde::Serialize;
+use sha1::Sha1;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::SecureStore;
+
+/// The Electron shell's store in its `userData` directory.
+const LEGACY_FILE: &str = "secure-store.json";
+
+/// Points at the Electron shell's `userData` directory if it is not in a usual place.
+const USER_DATA_VAR: &str = "DESKTOP_ELECTRON_USER_DATA";
+
+/// Names the Electron shell ran under: its `userData` directory below the
+/// platform's config directory, and the name its `safeStorage` key is kept under.
+const APP_NAMES: &[&str] = &["SmartOps", "@smartops/desktop"];
+
+/// Chromium's fixed salt and IV for `safeStorage` (`os_crypt`).
+const SALT: &[u8] = b"saltysalt";
+const IV: [u8; 16] = [b' '; 16];
+
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+/// Outcome of importing the Electron shell's secure store.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationReport {
+    pub source: PathBuf,
+    /// The tenant every entry was imported into: the one that opened at startup,
+    /// since the Electron store does not say which tenant an entry belonged to.
+    pub tenant_id: String,
+    /// Keys now in the tenant's entries.
+    pub migrated: Vec<String>,
+    /// Keys left behind, with why.
+    pub failed: Vec<FailedEntry>,
+    /// Where the old file was moved.
+    pub archived_to: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedEntry {
+    pub key: String,
+    pub reason: String,
+}
+
+/// The Electron shell's store, with the app name it was written under.
+pub fn find() -> Option<(PathBuf, &'static str)> {
+    if let Some(dir) = std::env::var_os(USER_DATA_VAR) {
+        let path = PathBuf::from(dir).join(LEGACY_FILE);
+        return path.is_file().then_some((path, APP_NAMES[0]));
+    }
+    let config_dir = dirs::config_dir()?;
+    APP_NAMES
+        .iter()
+        .map(|name| (config_dir.join(name).join(LEGACY_FILE), *name))
+        .find(|(path, _)| path.is_file())
+}
+
+/// Moves every entry of the Electron store at `path` into `tenant_id`'s entries
+/// in `store`, then archives the file so it is not imported again.
+pub fn import(path: &Path, app_name: &str, store: &SecureStore, tenant_id: &str) -> Result<MigrationReport, String> {
+    let json = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let entries: BTreeMap<String, String> =
+        serde_json::from_str(&json).map_err(|e| format!("{} is not an Electron secure store: {}", path.display(), e))?;
+
+    let mut report = MigrationReport {
+        source: path.to_path_buf(),
+        tenant_id: tenant_id.to_string(),
+        ..Default::default()
+    };
+    let prefix = SecureStore::web_prefix(tenant_id);
+    let mut keys = Keys::new(app_name);
+    for (key, encoded) in entries {
+        let result = super::validate_key(&key)
+            .and_then(|()| STANDARD.decode(&encoded).map_err(|_| "the value is not base64".to_string()))
+            .and_then(|data| keys.decrypt(&data))
+            .and_then(|value| store.set(&format!("{}{}", prefix, key), value));
+        match result {
+            Ok(()) => report.migrated.push(key),
+            Err(reason) => report.failed.push(FailedEntry { key, reason }),
+        }
+    }
+
+    let archive = archive_path(path);
+    fs::rename(path, &archive).map_err(|e| format!("cannot archive {}: {}", path.display(), e))?;
+    report.archived_to = Some(archive);
+    Ok(report)
+}
+
+fn archive_path(path: &Path) -> PathBuf {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".migrated-{}", stamp));
+    PathBuf::from(name)
+}
+
+/// The keys `safeStorage` may have used, each looked up once.
+#[cfg_attr(not(any(target_os = "linux", target_os = "macos")), allow(dead_code))]
+struct Keys<'a> {
+    app_name: &'a str,
+    /// The app's secret from the OS keyring, once looked up.
+    keyring: Option<Result<Vec<u8>, String>>,
+}
+
+impl<'a> Keys<'a> {
+    fn new(app_name: &'a str) -> Self {
+        Self { app_name, keyring: None }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn keyring_secret(&mut self) -> Result<Vec<u8>, String> {
+        let app_name = self.app_name;
+        self.keyring.get_or_insert_with(|| keyring_secret(app_name)).clone()
+    }
+
+    /// Decrypts a value Chromium's `os_crypt` wrote on Linux.
+    ///
+    /// `v10` uses the fixed "peanuts" password of the basic backend, `v11` the
+    /// secret Electron kept in the Secret Service, or an empty one if it had none.
+    #[cfg(target_os = "linux")]
+    fn decrypt(&mut self, data: &[u8]) -> Result<String, String> {
+        if let Some(ciphertext) = data.strip_prefix(b"v10") {
+            return decrypt_cbc(&derive_key(b"peanuts", 1), ciphertext);
+        }
+        let Some(ciphertext) = data.strip_prefix(b"v11") else {
+            return Err("unknown encryption scheme".to_string());
+        };
+        match self.keyring_secret() {
+            Ok(secret) => decrypt_cbc(&derive_key(&secret, 1), ciphertext),
+            Err(e) => decrypt_cbc(&derive_key(b"", 1), ciphertext)
+                .map_err(|_| format!("it needs the Electron key from the keyring: {}", e)),
+        }
+    }
+
+    /// Decrypts a value Chromium's `os_crypt` wrote on macOS, keyed from the Keychain.
+    #[cfg(target_os = "macos")]
+    fn decrypt(&mut self, data: &[u8]) -> Result<String, String> {
+        let Some(ciphertext) = data.strip_prefix(b"v10") else {
+            return Err("unknown encryption scheme".to_string());
+        };
+        let secret = self.keyring_secret().map_err(|e| format!("it needs the Electron key from the Keychain: {}", e))?;
+        decrypt_cbc(&derive_key(&secret, 1003), ciphertext)
+    }
+
+    /// Windows values are sealed with DPAPI and AES-GCM, which are not read here.
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    fn decrypt(&mut self, _data: &[u8]) -> Result<String, String> {
+        Err("values of the Electron shell cannot be decrypted on this platform".to_string())
+    }
+}
+
+/// Electron's `safeStorage` secret in the Secret Service, found by its `application` attribute.
+///
+/// Talks to `secret-service` directly: `keyring` only finds items by its own
+/// `service` and `username` attributes, which Electron does not set.
+#[cfg(target_os = "linux")]
+fn keyring_secret(app_name: &str) -> Result<Vec<u8>, String> {
+    use secret_service::blocking::SecretService;
+    use secret_service::EncryptionType;
+    use std::collections::HashMap;
+
+    let service = SecretService::connect(EncryptionType::Dh).map_err(|e| e.to_string())?;
+    let found = service
+        .search_items(HashMap::from([("application", app_name)]))
+        .map_err(|e| e.to_string())?;
+    match found.unlocked.first() {
+        Some(item) => item.get_secret().map_err(|e| e.to_string()),
+        None if !found.locked.is_empty() => Err("the keyring is locked".to_string()),
+        None => Err(format!("there is no \"{} Safe Storage\" entry", app_name)),
+    }
+}
+
+/// Electron's `safeStorage` secret in the Keychain.
+#[cfg(target_os = "macos")]
+fn keyring_secret(app_name: &str) -> Result<Vec<u8>, String> {
+    let service = format!("{} Safe Storage", app_name);
+    let mut last_error = String::new();
+    for account in [format!("{} Key", app_name), app_name.to_string()] {
+        match keyring::Entry::new(&service, &account).and_then(|entry| entry.get_password()) {
+            Ok(secret) => return Ok(secret.into_bytes()),
+            Err(e) => last_error = e.to_string(),
+        }
+    }
+    Err(last_error)
+}
+
+/// Chromium's `os_crypt` key: PBKDF2-HMAC-SHA1 of `password` over the fixed salt.
+fn derive_key(password: &[u8], rounds: u32) -> [u8; 16] {
+    let mut key = [0u8; 16];
+    pbkdf2::pbkdf2_hmac::<Sha1>(password, SALT, rounds, &mut key);
+    key
+}
+
+fn decrypt_cbc(key: &[u8; 16], ciphertext: &[u8]) -> Result<String, String> {
+    let plaintext = Aes128CbcDec::new(key.into(), &IV.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|_| "it does not decrypt with the Electron key".to_string())?;
+    String::from_utf8(plaintext).map_err(|_| "it does not decrypt with the Electron key".to_string())
+}
+
+/// Logs what an import did.
+pub fn log(report: &MigrationReport) {
+    info!(
+        "Imported {} entries of the Electron secure store {} into tenant {}",
+        report.migrated.len(),
+        report.source.display(),
+        report.tenant_id
+    );
+    for failed in &report.failed {
+        warn!("Could not import Electron secure store entry {}: {}", failed.key, failed.reason);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::cipher::BlockEncryptMut;
+
+    type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+
+    /// What Electron's `safeStorage.encryptString` stores with the basic Linux backend.
+    fn v10(value: &str) -> String {
+        let ciphertext = Aes128CbcEnc::new(&derive_key(b"peanuts", 1).into(), &IV.into())
+            .encrypt_padded_vec_mut::<Pkcs7>(value.as_bytes());
+        STANDARD.encode([b"v10".as_slice(), &ciphertext].concat())
+    }
+
+    #[test]
+    fn derives_chromiums_basic_key() {
+        // The AES key Chromium derives from the "peanuts" password.
+        assert_eq!(
+            derive_key(b"peanuts", 1),
+            [0xfd, 0x62, 0x1f, 0xe5, 0xa2, 0xb4, 0x02, 0x53, 0x9d, 0xfa, 0x14, 0x7c, 0xa9, 0x27, 0x27, 0x78]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn imports_basic_entries_and_archives_the_file() {
+        let dir = std::env::temp_dir().join(format!("smartops-legacy-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(LEGACY_FILE);
+        let legacy = serde_json::json!({
+            "vault-token": v10("hvs.s3cret"),
+            "not base64": "!!",
+            "garbled": STANDARD.encode(b"v10not a ciphertext"),
+            "unknown": STANDARD.encode(b"v99abc"),
+        });
+        fs::write(&path, legacy.to_string()).unwrap();
+
+        let store = SecureStore::default();
+        let report = import(&path, "SmartOps", &store, "acme").unwrap();
+
+        assert_eq!(report.tenant_id, "acme");
+        assert_eq!(report.migrated, ["vault-token"]);
+        assert_eq!(store.get("tenant:acme:web:vault-token").as_deref(), Some("hvs.s3cret"));
+        let failed: Vec<&str> = report.failed.iter().map(|f| f.key.as_str()).collect();
+        assert_eq!(failed, ["garbled", "not base64", "unknown"]);
+
+        assert!(!path.exists());
+        assert!(report.archived_to.unwrap().exists());
+    }
+}
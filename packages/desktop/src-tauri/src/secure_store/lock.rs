@@ -0,0 +1,220 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// PBKDF2-HMAC-SHA256 rounds for new passphrases (OWASP, 2023).
+const ROUNDS: u32 = 600_000;
+
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+/// Tracks when the store was last used and locks it once it has been idle too long.
+pub struct IdleLock {
+    last_used: Instant,
+    locked: bool,
+}
+
+impl Default for IdleLock {
+    fn default() -> Self {
+        Self {
+            last_used: Instant::now(),
+            locked: false,
+        }
+    }
+}
+
+impl IdleLock {
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Locks if the store has not been used for `limit`; true if it just locked.
+    pub fn lock_if_idle(&mut self, now: Instant, limit: Option<Duration>) -> bool {
+        let idle = limit.is_some_and(|limit| now.saturating_duration_since(self.last_used) >= limit);
+        if idle && !self.locked {
+            self.locked = true;
+            return true;
+        }
+        false
+    }
+
+    /// Records a use at `now`; false if the store is, or has just become, locked.
+    pub fn touch(&mut self, now: Instant, limit: Option<Duration>) -> bool {
+        self.lock_if_idle(now, limit);
+        if !self.locked {
+            self.last_used = now;
+        }
+        !self.locked
+    }
+
+    pub fn unlock(&mut self, now: Instant) {
+        self.locked = false;
+        self.last_used = now;
+    }
+}
+
+/// The idle lock of each tenant, so unlocking one tenant's secrets leaves the others locked.
+pub struct TenantLocks {
+    /// When the store was opened; a tenant that has not used it since counts as idle from then.
+    opened: Instant,
+    locks: HashMap<String, IdleLock>,
+}
+
+impl Default for TenantLocks {
+    fn default() -> Self {
+        Self {
+            opened: Instant::now(),
+            locks: HashMap::new(),
+        }
+    }
+}
+
+impl TenantLocks {
+    fn lock(&mut self, tenant_id: &str) -> &mut IdleLock {
+        let opened = self.opened;
+        self.locks.entry(tenant_id.to_string()).or_insert_with(|| IdleLock {
+            last_used: opened,
+            locked: false,
+        })
+    }
+
+    /// Locks the tenant if it has been idle for `limit`, then reports whether it is locked.
+    pub fn is_locked(&mut self, tenant_id: &str, now: Instant, limit: Option<Duration>) -> bool {
+        let lock = self.lock(tenant_id);
+        lock.lock_if_idle(now, limit);
+        lock.is_locked()
+    }
+
+    /// Locks every tenant in `tenant_ids` idle for `limit`; returns the ones that just locked.
+    pub fn lock_idle<'a>(
+        &mut self,
+        tenant_ids: impl IntoIterator<Item = &'a str>,
+        now: Instant,
+        limit: Option<Duration>,
+    ) -> Vec<String> {
+        tenant_ids
+            .into_iter()
+            .filter(|tenant_id| self.lock(tenant_id).lock_if_idle(now, limit))
+            .map(String::from)
+            .collect()
+    }
+
+    /// Records a use by the tenant at `now`; false if it is, or has just become, locked.
+    pub fn touch(&mut self, tenant_id: &str, now: Instant, limit: Option<Duration>) -> bool {
+        self.lock(tenant_id).touch(now, limit)
+    }
+
+    pub fn unlock(&mut self, tenant_id: &str, now: Instant) {
+        self.lock(tenant_id).unlock(now);
+    }
+}
+
+/// A tenant's local passphrase that unlocks its secrets, as kept in the store itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Passphrase {
+    salt: String,
+    rounds: u32,
+    hash: String,
+}
+
+impl Passphrase {
+    /// Hashes `passphrase` under a fresh salt. Slow on purpose.
+    pub fn new(passphrase: &str) -> Result<Self, String> {
+        Self::with_rounds(passphrase, ROUNDS)
+    }
+
+    fn with_rounds(passphrase: &str, rounds: u32) -> Result<Self, String> {
+        let mut salt = [0u8; SALT_LEN];
+        getrandom::getrandom(&mut salt).map_err(|e| format!("no randomness available: {}", e))?;
+        Ok(Self {
+            salt: STANDARD.encode(salt),
+            rounds,
+            hash: STANDARD.encode(derive(passphrase, &salt, rounds)),
+        })
+    }
+
+    /// Whether `passphrase` is this one. Slow on purpose.
+    pub fn verify(&self, passphrase: &str) -> bool {
+        let (Ok(salt), Ok(expected)) = (STANDARD.decode(&self.salt), STANDARD.decode(&self.hash)) else {
+            return false;
+        };
+        let actual = derive(passphrase, &salt, self.rounds);
+        // Compare every byte so timing does not tell how much matched.
+        expected.len() == actual.len() && expected.iter().zip(actual).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+}
+
+fn derive(passphrase: &str, salt: &[u8], rounds: u32) -> [u8; HASH_LEN] {
+    let mut hash = [0u8; HASH_LEN];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, rounds, &mut hash);
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locks_after_the_idle_limit() {
+        let start = Instant::now();
+        let limit = Some(Duration::from_secs(300));
+        let mut lock = IdleLock {
+            last_used: start,
+            locked: false,
+        };
+
+        assert!(lock.touch(start + Duration::from_secs(200), limit));
+        assert!(!lock.lock_if_idle(start + Duration::from_secs(400), limit));
+        assert!(lock.lock_if_idle(start + Duration::from_secs(500), limit));
+        assert!(!lock.lock_if_idle(start + Duration::from_secs(600), limit));
+        assert!(!lock.touch(start + Duration::from_secs(600), limit));
+
+        lock.unlock(start + Duration::from_secs(700));
+        assert!(lock.touch(start + Duration::from_secs(900), limit));
+        assert!(!lock.touch(start + Duration::from_secs(1_300), limit));
+    }
+
+    #[test]
+    fn never_locks_without_a_limit() {
+        let start = Instant::now();
+        let mut lock = IdleLock::default();
+        assert!(lock.touch(start + Duration::from_secs(86_400), None));
+        assert!(!lock.is_locked());
+    }
+
+    #[test]
+    fn tenants_lock_and_unlock_on_their_own() {
+        let limit = Some(Duration::from_secs(300));
+        let mut locks = TenantLocks::default();
+        let start = locks.opened;
+
+        assert!(locks.touch("acme", start + Duration::from_secs(200), limit));
+        let locked = locks.lock_idle(["acme", "globex"], start + Duration::from_secs(400), limit);
+        assert_eq!(locked, ["globex"]);
+        assert!(locks.is_locked("globex", start + Duration::from_secs(400), limit));
+        assert!(!locks.is_locked("acme", start + Duration::from_secs(400), limit));
+
+        // A tenant that never used the store counts as idle since it was opened.
+        assert!(!locks.touch("initech", start + Duration::from_secs(400), limit));
+
+        locks.unlock("globex", start + Duration::from_secs(450));
+        assert!(locks.touch("globex", start + Duration::from_secs(460), limit));
+        assert!(!locks.touch("initech", start + Duration::from_secs(460), limit));
+        assert!(!locks.touch("acme", start + Duration::from_secs(600), limit));
+    }
+
+    #[test]
+    fn passphrases_verify() {
+        let passphrase = Passphrase::with_rounds("correct horse", 1_000).unwrap();
+        assert!(passphrase.verify("correct horse"));
+        assert!(!passphrase.verify("correct horse "));
+        assert!(!passphrase.verify(""));
+
+        let again = Passphrase::with_rounds("correct horse", 1_000).unwrap();
+        assert_ne!(passphrase.salt, again.salt);
+    }
+}
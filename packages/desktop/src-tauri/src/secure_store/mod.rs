@@ -0,0 +1,765 @@
+mod file;
+mod key;
+mod legacy;
+mod lock;
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State, WebviewWindow};
+
+use crate::config::ConfigState;
+use crate::{auth, windows};
+use file::{Entries, Entry, LoadError, StoreFile};
+use key::KeySource;
+use legacy::MigrationReport;
+use lock::{Passphrase, TenantLocks};
+
+/// Name of the store file in the app data directory.
+const FILE_NAME: &str = "secure-store.dat";
+
+/// Longest key a web app may use.
+const MAX_KEY_LEN: usize = 128;
+
+/// Longest label of an entry.
+const MAX_LABEL_LEN: usize = 200;
+
+/// Shortest local passphrase.
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// How often expired entries are purged and the idle locks are checked.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+pub(crate) const LOCKED: &str = "The secure store is locked; unlock it to use secrets";
+
+/// Secrets kept for the webview and the shell, encrypted at rest.
+///
+/// The entries live in `secure-store.dat`, encrypted with a key whose secret
+/// is kept in the OS keyring, or in a user-only key file where there is none.
+/// Every change is written before it is reported as done.
+#[derive(Default)]
+pub struct SecureStore(Mutex<Inner>);
+
+/// The outcome of importing the Electron shell's store, if there was one this run.
+#[derive(Default)]
+pub struct Migration(Mutex<Option<MigrationReport>>);
+
+#[derive(Default)]
+struct Inner {
+    entries: Entries,
+    /// `None` while the store cannot be persisted; changes then last until exit.
+    file: Option<StoreFile>,
+    locks: TenantLocks,
+}
+
+impl SecureStore {
+    /// Opens the store in the app data directory, creating it on first use.
+    ///
+    /// Never fails: a store that cannot be opened is replaced by one kept in memory.
+    pub fn open(app: &AppHandle) -> Self {
+        match app.path().app_data_dir() {
+            Ok(dir) => Self::open_at(&dir.join(FILE_NAME), key::preferred()),
+            Err(e) => {
+                error!("No app data directory for the secure store, keeping secrets in memory: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    fn open_at(path: &Path, preferred: KeySource) -> Self {
+        let (file, entries) = match StoreFile::open(path) {
+            Ok(Some((file, entries))) => (Some(file), entries),
+            Ok(None) => (create(path, preferred), Entries::new()),
+            Err(LoadError::Corrupt(reason)) => {
+                let aside = set_aside(path);
+                warn!(
+                    "The secure store {} is unreadable because {}; moved it to {} and started an empty one",
+                    path.display(),
+                    reason,
+                    aside.display()
+                );
+                (create(path, preferred), Entries::new())
+            }
+            Err(LoadError::Unavailable(reason)) => {
+                // Leave the file alone, it may be readable on the next start.
+                error!(
+                    "Cannot open the secure store {} ({}); keeping secrets in memory until exit",
+                    path.display(),
+                    reason
+                );
+                (None, Entries::new())
+            }
+        };
+        Self(Mutex::new(Inner {
+            entries,
+            file,
+            locks: TenantLocks::default(),
+        }))
+    }
+
+    /// Prefix of the keys that belong to a tenant, removed when it logs out.
+    pub(crate) fn tenant_prefix(tenant_id: &str) -> String {
+        format!("tenant:{}:", windows::storage_key(tenant_id))
+    }
+
+    /// Entry holding the tenant's local passphrase; outside its web prefix, so no web app can reach it.
+    fn passphrase_key(tenant_id: &str) -> String {
+        format!("{}passphrase", Self::tenant_prefix(tenant_id))
+    }
+
+    /// Prefix of the entries a tenant's web app stores, kept apart from the shell's own.
+    fn web_prefix(tenant_id: &str) -> String {
+        format!("{}web:", Self::tenant_prefix(tenant_id))
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<String> {
+        let inner = self.0.lock().ok()?;
+        let entry = inner.entries.get(key)?;
+        (!entry.is_expired(now())).then(|| entry.value.clone())
+    }
+
+    pub(crate) fn set(&self, key: &str, value: String) -> Result<(), String> {
+        self.set_entry(key, Entry::new(value))
+    }
+
+    fn set_entry(&self, key: &str, entry: Entry) -> Result<(), String> {
+        self.update(|entries| {
+            entries.insert(key.to_string(), entry);
+        })
+    }
+
+    pub(crate) fn remove(&self, key: &str) -> Result<(), String> {
+        self.update(|entries| {
+            entries.remove(key);
+        })
+    }
+
+    /// The live keys starting with `prefix`, without it, in order.
+    pub(crate) fn keys(&self, prefix: &str) -> Vec<KeyInfo> {
+        let Ok(inner) = self.0.lock() else { return Vec::new() };
+        let now = now();
+        inner
+            .entries
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .filter(|(_, entry)| !entry.is_expired(now))
+            .map(|(key, entry)| KeyInfo {
+                key: key[prefix.len()..].to_string(),
+                label: entry.label.clone(),
+                expires_at: entry.expires_at,
+            })
+            .collect()
+    }
+
+    /// Removes the entries that expired by `now` and returns how many there were.
+    fn purge_expired(&self, now: u64) -> Result<usize, String> {
+        let mut purged = 0;
+        self.update(|entries| {
+            let before = entries.len();
+            entries.retain(|_, entry| !entry.is_expired(now));
+            purged = before - entries.len();
+        })?;
+        Ok(purged)
+    }
+
+    pub(crate) fn remove_prefix(&self, prefix: &str) -> Result<(), String> {
+        self.update(|entries| entries.retain(|key, _| !key.starts_with(prefix)))
+    }
+
+    /// Records a use of the tenant's secrets; false if they are locked.
+    fn touch(&self, tenant_id: &str, idle_limit: Option<Duration>) -> bool {
+        self.0
+            .lock()
+            .is_ok_and(|mut inner| inner.locks.touch(tenant_id, Instant::now(), idle_limit))
+    }
+
+    /// Locks the tenants idle for `idle_limit` and returns the ones that just locked.
+    fn lock_idle(&self, tenant_ids: &[String], idle_limit: Option<Duration>) -> Vec<String> {
+        let Ok(mut inner) = self.0.lock() else { return Vec::new() };
+        inner
+            .locks
+            .lock_idle(tenant_ids.iter().map(String::as_str), Instant::now(), idle_limit)
+    }
+
+    /// Whether changes are written to disk rather than kept in memory.
+    fn is_persistent(&self) -> bool {
+        self.0.lock().is_ok_and(|inner| inner.file.is_some())
+    }
+
+    fn is_locked(&self, tenant_id: &str, idle_limit: Option<Duration>) -> bool {
+        self.0
+            .lock()
+            .map_or(true, |mut inner| inner.locks.is_locked(tenant_id, Instant::now(), idle_limit))
+    }
+
+    fn unlock(&self, tenant_id: &str) {
+        if let Ok(mut inner) = self.0.lock() {
+            inner.locks.unlock(tenant_id, Instant::now());
+        }
+    }
+
+    fn passphrase(&self, tenant_id: &str) -> Option<Passphrase> {
+        serde_json::from_str(&self.get(&Self::passphrase_key(tenant_id))?).ok()
+    }
+
+    /// Applies `change` and writes the result; on failure the store is left as it was.
+    fn update(&self, change: impl FnOnce(&mut Entries)) -> Result<(), String> {
+        let mut inner = self.0.lock().map_err(|e| e.to_string())?;
+        let mut entries = inner.entries.clone();
+        change(&mut entries);
+        if entries == inner.entries {
+            return Ok(());
+        }
+        if let Some(file) = &inner.file {
+            file.save(&entries)?;
+        }
+        inner.entries = entries;
+        Ok(())
+    }
+}
+
+fn create(path: &Path, preferred: KeySource) -> Option<StoreFile> {
+    match StoreFile::create(path, preferred) {
+        Ok(file) => {
+            info!("Created the secure store {}", path.display());
+            Some(file)
+        }
+        Err(e) => {
+            error!("Cannot create the secure store {}, keeping secrets in memory: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Moves an unreadable store out of the way, keeping it for inspection.
+fn set_aside(path: &Path) -> PathBuf {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".corrupt-{}", stamp));
+    let aside = PathBuf::from(name);
+    if let Err(e) = fs::rename(path, &aside) {
+        warn!("Could not move {} aside: {}", path.display(), e);
+    }
+    aside
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// `secureStore.autoLockMinutes`, if set.
+fn idle_limit(app: &AppHandle) -> Option<Duration> {
+    let state = app.try_state::<ConfigState>()?;
+    let loaded = state.0.lock().ok()?;
+    let minutes = loaded.config.secure_store.as_ref()?.auto_lock_minutes?;
+    Some(Duration::from_secs(minutes.saturating_mul(60)))
+}
+
+/// Whether the tenant's secrets are locked, without counting as a use; the
+/// shell then keeps the tenant's tokens from its web app too.
+pub(crate) fn tenant_locked(app: &AppHandle, tenant_id: &str) -> bool {
+    app.state::<SecureStore>().is_locked(tenant_id, idle_limit(app))
+}
+
+/// Records a use of the tenant's secrets on its web app's behalf; false while they are locked.
+pub(crate) fn touch_tenant(app: &AppHandle, tenant_id: &str) -> bool {
+    app.state::<SecureStore>().touch(tenant_id, idle_limit(app))
+}
+
+/// The ids of the configured tenants.
+fn tenant_ids(app: &AppHandle) -> Vec<String> {
+    let Some(state) = app.try_state::<ConfigState>() else { return Vec::new() };
+    let Ok(loaded) = state.0.lock() else { return Vec::new() };
+    loaded.config.tenants.keys().cloned().collect()
+}
+
+/// Purges expired entries and locks each tenant's secrets once they have been
+/// idle too long, sending `secure-store-locked` to that tenant's window.
+pub fn spawn_sweeper(app: &AppHandle) {
+    let app = app.clone();
+    let result = thread::Builder::new().name("secure-store-sweeper".to_string()).spawn(move || loop {
+        thread::sleep(SWEEP_INTERVAL);
+        let store = app.state::<SecureStore>();
+
+        match store.purge_expired(now()) {
+            Ok(0) => {}
+            Ok(purged) => info!("Removed {} expired secure store entries", purged),
+            Err(e) => warn!("Failed to remove expired secure store entries: {}", e),
+        }
+        for tenant_id in store.lock_idle(&tenant_ids(&app), idle_limit(&app)) {
+            info!("Locked the secure store of tenant {} after it was idle", tenant_id);
+            if let Err(e) = app.emit_to(windows::label(&tenant_id), "secure-store-locked", ()) {
+                error!("Failed to emit secure-store-locked: {}", e);
+            }
+        }
+    });
+
+    if let Err(e) = result {
+        error!("Failed to start the secure store sweeper: {}", e);
+    }
+}
+
+/// Imports the Electron shell's `secure-store.json` into `tenant_id`'s entries,
+/// the tenant the Electron shell showed, and archives it.
+///
+/// Runs in the background; the report is kept for `get_secure_store_migration`.
+pub fn import_legacy(app: &AppHandle, tenant_id: &str) {
+    let Some((path, app_name)) = legacy::find() else { return };
+    if !app.state::<SecureStore>().is_persistent() {
+        warn!("Not importing {} while the secure store cannot be saved", path.display());
+        return;
+    }
+
+    let app = app.clone();
+    let tenant_id = tenant_id.to_string();
+    let result = thread::Builder::new().name("secure-store-import".to_string()).spawn(move || {
+        match legacy::import(&path, app_name, &app.state::<SecureStore>(), &tenant_id) {
+            Ok(report) => {
+                legacy::log(&report);
+                if let Ok(mut migration) = app.state::<Migration>().0.lock() {
+                    *migration = Some(report);
+                }
+            }
+            Err(e) => error!("Failed to import the Electron secure store: {}", e),
+        }
+    });
+
+    if let Err(e) = result {
+        error!("Failed to start the secure store import: {}", e);
+    }
+}
+
+/// Checks a key from a web app: `[A-Za-z0-9][A-Za-z0-9._-]*`, at most `MAX_KEY_LEN` long.
+fn validate_key(key: &str) -> Result<(), String> {
+    let valid = key.len() <= MAX_KEY_LEN
+        && key.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    match valid {
+        true => Ok(()),
+        false => Err(format!(
+            "Invalid secure store key `{}`: use up to {} letters, digits, `.`, `_` and `-`, starting with a letter or digit",
+            key, MAX_KEY_LEN
+        )),
+    }
+}
+
+/// The tenant of the calling window.
+fn caller_tenant(app: &AppHandle, window: &WebviewWindow) -> Result<String, String> {
+    windows::tenant_id(app, window.label()).ok_or_else(|| "This window is not a tenant window".to_string())
+}
+
+/// Prefix of the calling window's entries.
+fn caller_prefix(app: &AppHandle, window: &WebviewWindow) -> Result<String, String> {
+    Ok(SecureStore::web_prefix(&caller_tenant(app, window)?))
+}
+
+/// Records a use of the calling tenant's secrets; fails while they are locked.
+fn touch_caller(app: &AppHandle, window: &WebviewWindow) -> Result<(), String> {
+    match touch_tenant(app, &caller_tenant(app, window)?) {
+        true => Ok(()),
+        false => Err(LOCKED.to_string()),
+    }
+}
+
+/// Where `key` of the calling window's tenant is stored.
+fn scoped_key(app: &AppHandle, window: &WebviewWindow, key: &str) -> Result<String, String> {
+    validate_key(key)?;
+    Ok(format!("{}{}", caller_prefix(app, window)?, key))
+}
+
+/// A key as listed to web apps: never its value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyInfo {
+    pub key: String,
+    pub label: Option<String>,
+    /// Unix timestamp after which the entry is gone.
+    pub expires_at: Option<u64>,
+}
+
+/// A tenant's secure store keys, as shown to administrators.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantKeys {
+    pub tenant_id: String,
+    pub keys: Vec<KeyInfo>,
+}
+
+/// Metadata accepted by `set_secure_store`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryOptions {
+    /// Seconds until the entry is removed.
+    pub ttl_secs: Option<u64>,
+    pub label: Option<String>,
+}
+
+impl EntryOptions {
+    fn into_entry(self, value: String, now: u64) -> Result<Entry, String> {
+        if self.ttl_secs == Some(0) {
+            return Err("ttlSecs must be at least 1".to_string());
+        }
+        if self.label.as_ref().is_some_and(|label| label.chars().count() > MAX_LABEL_LEN) {
+            return Err(format!("Labels may be at most {} characters long", MAX_LABEL_LEN));
+        }
+        Ok(Entry {
+            value,
+            label: self.label,
+            expires_at: self.ttl_secs.map(|ttl| now.saturating_add(ttl)),
+        })
+    }
+}
+
+/// Whether the calling tenant's secrets are locked, as shown to its web app.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockStatus {
+    pub locked: bool,
+    /// `secureStore.autoLockMinutes`; the store never locks without it.
+    pub auto_lock_minutes: Option<u64>,
+    /// Whether `unlock_secure_store` accepts a passphrase for this tenant.
+    pub has_passphrase: bool,
+}
+
+/// Reads one of the calling tenant's entries, unless the store is locked.
+#[tauri::command]
+pub fn get_secure_store(
+    key: String,
+    window: WebviewWindow,
+    app_handle: AppHandle,
+    store: State<'_, SecureStore>,
+) -> Result<Option<String>, String> {
+    let key = scoped_key(&app_handle, &window, &key)?;
+    touch_caller(&app_handle, &window)?;
+    Ok(store.get(&key))
+}
+
+/// Stores one of the calling tenant's entries, optionally with a TTL and a label, unless the store is locked.
+#[tauri::command]
+pub fn set_secure_store(
+    key: String,
+    value: String,
+    options: Option<EntryOptions>,
+    window: WebviewWindow,
+    app_handle: AppHandle,
+    store: State<'_, SecureStore>,
+) -> Result<(), String> {
+    let key = scoped_key(&app_handle, &window, &key)?;
+    let entry = options.unwrap_or_default().into_entry(value, now())?;
+    touch_caller(&app_handle, &window)?;
+    store.set_entry(&key, entry)
+}
+
+/// Removes one of the calling tenant's entries, unless the store is locked.
+#[tauri::command]
+pub fn delete_secure_store(
+    key: String,
+    window: WebviewWindow,
+    app_handle: AppHandle,
+    store: State<'_, SecureStore>,
+) -> Result<(), String> {
+    let key = scoped_key(&app_handle, &window, &key)?;
+    touch_caller(&app_handle, &window)?;
+    store.remove(&key)
+}
+
+/// Lists the keys the calling tenant has stored, with their labels and expiry, unless the store is locked.
+#[tauri::command]
+pub fn list_secure_keys(window: WebviewWindow, app_handle: AppHandle, store: State<'_, SecureStore>) -> Result<Vec<KeyInfo>, String> {
+    touch_caller(&app_handle, &window)?;
+    Ok(store.keys(&caller_prefix(&app_handle, &window)?))
+}
+
+/// Removes every entry the calling tenant has stored, unless the store is locked;
+/// the shell's own, such as its tokens, stay.
+#[tauri::command]
+pub fn clear_secure_store(window: WebviewWindow, app_handle: AppHandle, store: State<'_, SecureStore>) -> Result<(), String> {
+    touch_caller(&app_handle, &window)?;
+    store.remove_prefix(&caller_prefix(&app_handle, &window)?)
+}
+
+/// Lists the keys of every configured tenant, never their values, for the
+/// settings page of a user holding `keycloak.adminRole`.
+#[tauri::command]
+pub async fn list_all_secure_keys(window: WebviewWindow, app_handle: AppHandle) -> Result<Vec<TenantKeys>, String> {
+    let tenant_id = windows::tenant_id(&app_handle, window.label()).ok_or("This window is not a tenant window")?;
+
+    let app = app_handle.clone();
+    tauri::async_runtime::spawn_blocking(move || auth::require_admin(&app, &tenant_id))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let mut tenant_ids: Vec<String> = {
+        let state = app_handle.state::<ConfigState>();
+        let loaded = state.0.lock().map_err(|e| e.to_string())?;
+        loaded.config.tenants.keys().cloned().collect()
+    };
+    tenant_ids.sort();
+
+    let store = app_handle.state::<SecureStore>();
+    Ok(tenant_ids
+        .into_iter()
+        .map(|tenant_id| TenantKeys {
+            keys: store.keys(&SecureStore::web_prefix(&tenant_id)),
+            tenant_id,
+        })
+        .collect())
+}
+
+/// What importing the Electron shell's store did, if it was imported this run
+/// into the calling window's tenant; other tenants' windows never see it.
+#[tauri::command]
+pub fn get_secure_store_migration(
+    window: WebviewWindow,
+    app_handle: AppHandle,
+    migration: State<'_, Migration>,
+) -> Result<Option<MigrationReport>, String> {
+    let tenant_id = caller_tenant(&app_handle, &window)?;
+    let report = migration.0.lock().map_err(|e| e.to_string())?;
+    Ok(report.clone().filter(|report| report.tenant_id == tenant_id))
+}
+
+/// Whether the calling tenant's secrets are locked and how they can be unlocked.
+#[tauri::command]
+pub fn get_secure_store_status(
+    window: WebviewWindow,
+    app_handle: AppHandle,
+    store: State<'_, SecureStore>,
+) -> Result<LockStatus, String> {
+    let tenant_id = caller_tenant(&app_handle, &window)?;
+    let idle_limit = idle_limit(&app_handle);
+    Ok(LockStatus {
+        locked: store.is_locked(&tenant_id, idle_limit),
+        auto_lock_minutes: idle_limit.map(|limit| limit.as_secs() / 60),
+        has_passphrase: store.passphrase(&tenant_id).is_some(),
+    })
+}
+
+/// Checks `passphrase` against the tenant's local passphrase off the async runtime.
+async fn verify_passphrase(app: &AppHandle, tenant_id: &str, passphrase: String) -> Result<(), String> {
+    let stored = app
+        .state::<SecureStore>()
+        .passphrase(tenant_id)
+        .ok_or("No passphrase is set; log in again instead")?;
+    let matches = tauri::async_runtime::spawn_blocking(move || stored.verify(&passphrase))
+        .await
+        .map_err(|e| e.to_string())?;
+    if !matches {
+        warn!("Wrong secure store passphrase for tenant {}", tenant_id);
+        return Err("Wrong passphrase".to_string());
+    }
+    Ok(())
+}
+
+/// Unlocks the calling window's tenant's secrets with its local passphrase or,
+/// without one, by logging the tenant in again as the same user. Other tenants stay locked.
+#[tauri::command]
+pub async fn unlock_secure_store(passphrase: Option<String>, window: WebviewWindow, app_handle: AppHandle) -> Result<(), String> {
+    let tenant_id = caller_tenant(&app_handle, &window)?;
+    match passphrase {
+        Some(passphrase) => verify_passphrase(&app_handle, &tenant_id, passphrase).await?,
+        None => {
+            auth::reauthenticate(&app_handle, &tenant_id).await?;
+        }
+    }
+    unlock_tenant(&app_handle, &tenant_id)
+}
+
+/// Unlocks the tenant's secrets once its user proved who they are, sending
+/// `secure-store-unlocked` to the tenant's window.
+pub(crate) fn unlock_tenant(app: &AppHandle, tenant_id: &str) -> Result<(), String> {
+    app.state::<SecureStore>().unlock(tenant_id);
+    info!("Unlocked the secure store of tenant {}", tenant_id);
+    app.emit_to(windows::label(tenant_id), "secure-store-unlocked", ()).map_err(|e| e.to_string())
+}
+
+/// Sets the calling tenant's local passphrase; only while its secrets are unlocked.
+///
+/// Replacing a passphrase takes the current one or, without it, logging the
+/// tenant in again as the same user, so an unattended window cannot change it.
+#[tauri::command]
+pub async fn set_secure_store_passphrase(
+    passphrase: String,
+    current_passphrase: Option<String>,
+    window: WebviewWindow,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!("The passphrase must be at least {} characters long", MIN_PASSPHRASE_LEN));
+    }
+    let tenant_id = caller_tenant(&app_handle, &window)?;
+    let store = app_handle.state::<SecureStore>();
+    if !store.touch(&tenant_id, idle_limit(&app_handle)) {
+        return Err(LOCKED.to_string());
+    }
+
+    if store.passphrase(&tenant_id).is_some() {
+        match current_passphrase {
+            Some(current) => verify_passphrase(&app_handle, &tenant_id, current).await?,
+            None => {
+                auth::reauthenticate(&app_handle, &tenant_id).await?;
+            }
+        }
+    }
+
+    let hashed = tauri::async_runtime::spawn_blocking(move || Passphrase::new(&passphrase))
+        .await
+        .map_err(|e| e.to_string())??;
+    let json = serde_json::to_string(&hashed).map_err(|e| e.to_string())?;
+    store.set(&SecureStore::passphrase_key(&tenant_id), json)?;
+    info!("Set the secure store passphrase of tenant {}", tenant_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("smartops-secure-store-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn entries_survive_a_restart_encrypted() {
+        let path = temp_dir("restart").join(FILE_NAME);
+        let store = SecureStore::open_at(&path, KeySource::File);
+        store.set("tenant:acme:auth", "s3cret-token".to_string()).unwrap();
+        store.set("other", "value".to_string()).unwrap();
+        store.remove("other").unwrap();
+
+        let on_disk = fs::read_to_string(&path).unwrap();
+        assert!(!on_disk.contains("s3cret-token"));
+        assert!(!on_disk.contains("tenant:acme"));
+
+        let reopened = SecureStore::open_at(&path, KeySource::File);
+        assert_eq!(reopened.get("tenant:acme:auth").as_deref(), Some("s3cret-token"));
+        assert_eq!(reopened.get("other"), None);
+    }
+
+    #[test]
+    fn tampered_stores_are_set_aside() {
+        let dir = temp_dir("tampered");
+        let path = dir.join(FILE_NAME);
+        SecureStore::open_at(&path, KeySource::File).set("key", "value".to_string()).unwrap();
+
+        let mut envelope: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        let ciphertext = envelope["ciphertext"].as_str().unwrap();
+        let flipped = if ciphertext.starts_with('A') { "B" } else { "A" };
+        envelope["ciphertext"] = format!("{}{}", flipped, &ciphertext[1..]).into();
+        fs::write(&path, serde_json::to_vec(&envelope).unwrap()).unwrap();
+
+        assert!(matches!(StoreFile::open(&path), Err(LoadError::Corrupt(_))));
+        let store = SecureStore::open_at(&path, KeySource::File);
+        assert_eq!(store.get("key"), None);
+        let names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert!(names.iter().any(|name| name.starts_with("secure-store.dat.corrupt-")));
+
+        store.set("key", "new".to_string()).unwrap();
+        assert_eq!(SecureStore::open_at(&path, KeySource::File).get("key").as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn a_lost_key_makes_the_store_corrupt() {
+        let dir = temp_dir("lost-key");
+        let path = dir.join(FILE_NAME);
+        SecureStore::open_at(&path, KeySource::File).set("key", "value".to_string()).unwrap();
+
+        fs::remove_file(dir.join("secure-store.key")).unwrap();
+        assert!(matches!(StoreFile::open(&path), Err(LoadError::Corrupt(_))));
+    }
+
+    #[test]
+    fn writes_replace_the_file_in_one_step() {
+        let dir = temp_dir("atomic");
+        let path = dir.join(FILE_NAME);
+        let store = SecureStore::open_at(&path, KeySource::File);
+        store.set("key", "value".to_string()).unwrap();
+
+        assert!(!dir.join("secure-store.dat.tmp").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for name in [FILE_NAME, "secure-store.key"] {
+                let mode = fs::metadata(dir.join(name)).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600, "{} should be private", name);
+            }
+        }
+    }
+
+    #[test]
+    fn keys_are_validated() {
+        assert!(validate_key("kubeconfig").is_ok());
+        assert!(validate_key("vault.token_v2-prod").is_ok());
+        assert!(validate_key(&"a".repeat(MAX_KEY_LEN)).is_ok());
+
+        for key in ["", ".hidden", "-x", "a:b", "a/b", "tenant key", "ключ"] {
+            assert!(validate_key(key).is_err(), "{:?} should be rejected", key);
+        }
+        assert!(validate_key(&"a".repeat(MAX_KEY_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn tenants_only_see_their_own_keys() {
+        let store = SecureStore::default();
+        let acme = SecureStore::web_prefix("acme");
+        let other = SecureStore::web_prefix("acme-eu");
+        store.set(&format!("{}token", acme), "a".to_string()).unwrap();
+        store.set(&format!("{}kubeconfig", acme), "b".to_string()).unwrap();
+        store.set(&format!("{}token", other), "c".to_string()).unwrap();
+        store.set(&format!("{}auth", SecureStore::tenant_prefix("acme")), "d".to_string()).unwrap();
+
+        let keys = |prefix: &str| store.keys(prefix).into_iter().map(|info| info.key).collect::<Vec<_>>();
+        assert_eq!(keys(&acme), vec!["kubeconfig", "token"]);
+        assert_eq!(keys(&other), vec!["token"]);
+
+        store.remove_prefix(&acme).unwrap();
+        assert!(keys(&acme).is_empty());
+        assert_eq!(keys(&other), vec!["token"]);
+        assert!(store.get(&format!("{}auth", SecureStore::tenant_prefix("acme"))).is_some());
+    }
+
+    #[test]
+    fn expired_entries_are_hidden_and_purged() {
+        let store = SecureStore::default();
+        let options = |ttl_secs| EntryOptions {
+            ttl_secs,
+            label: Some("vcluster kubeconfig".to_string()),
+        };
+        store.set_entry("gone", options(Some(60)).into_entry("a".to_string(), now() - 120).unwrap()).unwrap();
+        store.set_entry("live", options(Some(3_600)).into_entry("b".to_string(), now()).unwrap()).unwrap();
+        store.set_entry("kept", options(None).into_entry("c".to_string(), now()).unwrap()).unwrap();
+
+        assert_eq!(store.get("gone"), None);
+        assert_eq!(store.get("live").as_deref(), Some("b"));
+        let keys = store.keys("");
+        assert_eq!(keys.iter().map(|info| info.key.as_str()).collect::<Vec<_>>(), vec!["kept", "live"]);
+        assert_eq!(keys[0].label.as_deref(), Some("vcluster kubeconfig"));
+        assert_eq!(keys[0].expires_at, None);
+
+        assert_eq!(store.purge_expired(now()).unwrap(), 1);
+        assert_eq!(store.purge_expired(now() + 7_200).unwrap(), 1);
+        assert_eq!(store.get("kept").as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn entry_options_are_validated() {
+        assert!(EntryOptions { ttl_secs: Some(0), label: None }.into_entry(String::new(), 0).is_err());
+        let label = Some("x".repeat(MAX_LABEL_LEN + 1));
+        assert!(EntryOptions { ttl_secs: None, label }.into_entry(String::new(), 0).is_err());
+        let entry = EntryOptions { ttl_secs: Some(30), label: None }.into_entry("v".to_string(), 100).unwrap();
+        assert_eq!(entry.expires_at, Some(130));
+    }
+}
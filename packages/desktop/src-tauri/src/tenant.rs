@@ -0,0 +1,562 @@
+use log::{error, info, warn};
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tauri_plugin_store::StoreExt;
+
+use crate::config::{self, ConfigLayer, ConfigState, DesktopConfig, LoadedConfig, TenantConfig, TenantMismatch};
+use crate::{auth, deep_link, diagnostics, windows};
+
+/// Store file for small pieces of app state that outlive a session.
+pub const STATE_STORE: &str = "desktop-state.json";
+
+const LAST_TENANT_KEY: &str = "lastTenant";
+
+/// Prefix of the tray menu item ids in the Tenants submenu.
+pub const MENU_ID_PREFIX: &str = "tenant:";
+
+/// The tenant the main window is currently showing.
+#[derive(Default)]
+pub struct ActiveTenant(pub Mutex<String>);
+
+/// Picks the tenant to open at startup.
+///
+/// The tenant of a `smartops://` launch link wins, then `--tenant` and
+/// `DESKTOP_TENANT`, then the tenant used last time if it is still configured,
+/// then `default_tenant`.
+pub fn initial_id(app: &AppHandle, config: &DesktopConfig) -> String {
+    deep_link::startup_tenant(app, config)
+        .or_else(config::requested_tenant_id)
+        .or_else(|| last_tenant(app).filter(|id| config.tenants.contains_key(id)))
+        .unwrap_or_else(|| config.default_tenant.clone())
+}
+
+/// Returns the active tenant id once startup has completed.
+pub fn active_id(app: &AppHandle) -> Option<String> {
+    let state = app.try_state::<ActiveTenant>()?;
+    let id = state.0.lock().ok()?;
+    Some(id.clone())
+}
+
+/// Window title for a tenant.
+pub fn window_title(tenant_name: &str) -> String {
+    format!("SmartOps - {}", tenant_name)
+}
+
+/// Replaces the main window with one for another tenant and remembers the choice.
+///
+/// Each tenant window has its own storage, so the old window is closed rather than navigated.
+///
+/// Emits `tenant-changed` with the new tenant's id, name and app URL.
+pub fn switch(app: &AppHandle, tenant_id: &str) -> Result<(), String> {
+    let tenant = {
+        let state = app.state::<ConfigState>();
+        let loaded = state.0.lock().map_err(|e| e.to_string())?;
+        loaded.config.tenant(tenant_id)?.clone()
+    };
+
+    if active_id(app).as_deref() == Some(tenant_id) {
+        return Ok(());
+    }
+
+    info!("Switching to tenant {} ({})", tenant_id, tenant.app_url);
+    let previous = windows::main(app);
+    let window = windows::open(app, tenant_id, &tenant).map_err(|e| e.to_string())?;
+    let _ = window.show();
+    let _ = window.set_focus();
+
+    if let Ok(mut active) = app.state::<ActiveTenant>().0.lock() {
+        *active = tenant_id.to_string();
+    }
+    if let Some(previous) = previous {
+        let _ = previous.destroy();
+    }
+
+    remember(app, tenant_id);
+    crate::refresh_tray(app);
+
+    app.emit(
+        "tenant-changed",
+        serde_json::json!({
+            "tenantId": tenant_id,
+            "appUrl": tenant.app_url,
+            "tenantName": tenant.name.as_deref().unwrap_or(tenant_id),
+        }),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Shows another tenant in `window`: the main window switches, other tenant
+/// windows are replaced by a window for the new tenant.
+pub fn switch_window(app: &AppHandle, window: &WebviewWindow, tenant_id: &str) -> Result<(), String> {
+    if windows::main(app).is_some_and(|main| main.label() == window.label()) {
+        return switch(app, tenant_id);
+    }
+
+    windows::open_in_new_window(app, tenant_id)?;
+    if window.label() != windows::label(tenant_id) {
+        let _ = window.destroy();
+    }
+    Ok(())
+}
+
+/// Async so the new window is not built on the main thread, which deadlocks on Windows.
+#[tauri::command]
+pub async fn switch_tenant(tenant_id: String, window: WebviewWindow, app_handle: AppHandle) -> Result<(), String> {
+    if window.label().starts_with(windows::LABEL_PREFIX) {
+        switch_window(&app_handle, &window, &tenant_id)
+    } else {
+        switch(&app_handle, &tenant_id)
+    }
+}
+
+/// What `select_tenant_from_token` did.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "outcome", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum TokenTenantOutcome {
+    /// No tenant claim is configured, or the session's token does not carry it.
+    NoClaim,
+    /// The token belongs to the window's tenant.
+    Matched { tenant_id: String },
+    /// The window was replaced by one for the token's tenant.
+    Switched { tenant_id: String },
+    /// The user chose to stay on the current tenant.
+    Declined { tenant_id: String },
+}
+
+/// Checks the configured `keycloak.tenantClaim` of the calling window's
+/// session against the tenant the window shows.
+///
+/// The claim is read from the access token of the shell's own login after it
+/// is verified against the realm's signing keys, so the web app cannot pick
+/// the tenant. On a mismatch the window is switched to the token's tenant,
+/// after asking unless `keycloak.onTenantMismatch` is `switch`. A claim naming
+/// a tenant that is not configured is reported to the user and returned as an error.
+#[tauri::command]
+pub async fn select_tenant_from_token(
+    window: WebviewWindow,
+    app_handle: AppHandle,
+) -> Result<TokenTenantOutcome, String> {
+    let window_tenant = windows::tenant_id(&app_handle, window.label()).ok_or("This window is not a tenant window")?;
+    let on_mismatch = {
+        let state = app_handle.state::<ConfigState>();
+        let loaded = state.0.lock().map_err(|e| e.to_string())?;
+        loaded.config.keycloak.as_ref().map(|k| k.on_tenant_mismatch).unwrap_or_default()
+    };
+
+    let app = app_handle.clone();
+    let id = window_tenant.clone();
+    let identity = tauri::async_runtime::spawn_blocking(move || auth::identity(&app, &id))
+        .await
+        .map_err(|e| e.to_string())??;
+    let Some(tenant_id) = identity.tenant else {
+        warn!("The session of tenant {} has no tenant claim", identity.tenant_id);
+        return Ok(TokenTenantOutcome::NoClaim);
+    };
+
+    if window.label() == windows::label(&tenant_id) {
+        return Ok(TokenTenantOutcome::Matched { tenant_id });
+    }
+
+    let (current_name, tenant_name) = {
+        let state = app_handle.state::<ConfigState>();
+        let loaded = state.0.lock().map_err(|e| e.to_string())?;
+        let current_name = loaded.config.tenants.get(&window_tenant).and_then(|t| t.name.clone());
+        let current_name = current_name.unwrap_or_else(|| window_tenant.clone());
+        match loaded.config.tenant(&tenant_id) {
+            Ok(tenant) => (current_name, tenant.name.clone().unwrap_or_else(|| tenant_id.clone())),
+            Err(e) => {
+                let message = format!(
+                    "Your account belongs to tenant \"{}\", which is not configured in SmartOps Desktop. \
+                     Ask your administrator to add it.",
+                    tenant_id
+                );
+                error!("Token names an unknown tenant: {}", e);
+                app_handle
+                    .dialog()
+                    .message(&message)
+                    .title("Unknown tenant")
+                    .kind(MessageDialogKind::Error)
+                    .show(|_| {});
+                return Err(message);
+            }
+        }
+    };
+
+    if on_mismatch == TenantMismatch::Prompt {
+        let app = app_handle.clone();
+        let switch = tauri::async_runtime::spawn_blocking(move || {
+            app.dialog()
+                .message(format!(
+                    "You signed in with an account for {}, but this window shows {}. Switch it to {}?",
+                    tenant_name, current_name, tenant_name
+                ))
+                .title("Switch tenant")
+                .kind(MessageDialogKind::Info)
+                .buttons(MessageDialogButtons::OkCancelCustom("Switch".into(), "Stay".into()))
+                .blocking_show()
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+        if !switch {
+            info!("User stayed on the current tenant instead of {}", tenant_id);
+            return Ok(TokenTenantOutcome::Declined { tenant_id });
+        }
+    }
+
+    switch_window(&app_handle, &window, &tenant_id)?;
+    Ok(TokenTenantOutcome::Switched { tenant_id })
+}
+
+/// A configured tenant as shown in tenant management.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantEntry {
+    pub id: String,
+    #[serde(flatten)]
+    pub tenant: TenantConfig,
+    /// Highest-precedence layer that sets one of the tenant's values.
+    pub source: ConfigLayer,
+    /// Set by the system config, the remote catalogue, env or the command line,
+    /// so it cannot be changed from the app.
+    pub locked: bool,
+    /// Defined only in the user config, so it can be removed.
+    pub removable: bool,
+}
+
+/// Lists the configured tenants, or none before the first one is added.
+#[tauri::command]
+pub fn list_tenants(app_handle: AppHandle) -> Result<Vec<TenantEntry>, String> {
+    let Some(state) = app_handle.try_state::<ConfigState>() else { return Ok(Vec::new()) };
+    let loaded = state.0.lock().map_err(|e| e.to_string())?;
+    Ok(entries(&loaded))
+}
+
+/// The tenants of `loaded` with the layers they come from, sorted by id.
+fn entries(loaded: &LoadedConfig) -> Vec<TenantEntry> {
+    let mut entries: Vec<TenantEntry> = loaded
+        .config
+        .tenants
+        .iter()
+        .map(|(id, tenant)| {
+            let layers = loaded.tenant_layers(id);
+            TenantEntry {
+                id: id.clone(),
+                tenant: tenant.clone(),
+                source: layers.last().copied().unwrap_or(ConfigLayer::Bundled),
+                locked: layers.iter().any(|layer| layer.is_managed()),
+                removable: layers.iter().all(|layer| *layer == ConfigLayer::User),
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+    entries
+}
+
+/// Lets the shell's own setup and diagnostics window manage tenants, and
+/// otherwise only a user holding `keycloak.adminRole` in `tenant_id`'s own
+/// window, so one tenant's page can never repoint another tenant.
+///
+/// `tenant_id` is `None` when adding a tenant, which no window shows yet.
+async fn authorize(app: &AppHandle, window: &WebviewWindow, tenant_id: Option<&str>) -> Result<(), String> {
+    if window.label() == diagnostics::WINDOW_LABEL {
+        return Ok(());
+    }
+    let caller = windows::tenant_id(app, window.label()).ok_or("This window is not a tenant window")?;
+    if let Some(tenant_id) = tenant_id.filter(|id| *id != caller) {
+        return Err(format!("A window of tenant `{}` cannot change tenant `{}`", caller, tenant_id));
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || auth::require_admin(&app, &caller))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Cannot manage tenants: {}", e))
+}
+
+/// Adds a tenant to the user config.
+///
+/// Before any config could be loaded this is the first-run setup: the tenant
+/// also becomes `defaultTenant`, and `retry_startup` picks it up. Async, like
+/// the other edits, as reloading may fetch the remote catalogue.
+#[tauri::command]
+pub async fn add_tenant(
+    tenant_id: String,
+    tenant: TenantConfig,
+    window: WebviewWindow,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    authorize(&app_handle, &window, None).await?;
+    tauri::async_runtime::spawn_blocking(move || add(&app_handle, &tenant_id, tenant))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn add(app: &AppHandle, tenant_id: &str, tenant: TenantConfig) -> Result<(), String> {
+    let tenant_id = tenant_id.trim().to_string();
+    config::user::validate_tenant_id(&tenant_id)?;
+    let tenant = config::user::normalize_tenant(tenant)?;
+
+    let first_run = match app.try_state::<ConfigState>() {
+        Some(state) => {
+            let loaded = state.0.lock().map_err(|e| e.to_string())?;
+            if loaded.config.tenants.contains_key(&tenant_id) {
+                return Err(format!("Tenant `{}` already exists", tenant_id));
+            }
+            false
+        }
+        None => true,
+    };
+
+    edit_user_config(app, |doc| {
+        config::user::set_tenant(doc, &tenant_id, &tenant)?;
+        if first_run {
+            doc.entry("env").or_insert_with(|| config::current_env().into());
+            doc.entry("defaultTenant").or_insert_with(|| tenant_id.clone().into());
+        }
+        Ok(())
+    })?;
+
+    info!("Added tenant {} ({})", tenant_id, tenant.app_url);
+    Ok(())
+}
+
+/// Replaces a tenant's settings in the user config.
+///
+/// Tenants from the bundled config are overridden rather than changed; locked
+/// tenants are refused. A changed `appUrl` applies the next time the tenant's
+/// window is opened.
+#[tauri::command]
+pub async fn update_tenant(
+    tenant_id: String,
+    tenant: TenantConfig,
+    window: WebviewWindow,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    authorize(&app_handle, &window, Some(&tenant_id)).await?;
+    tauri::async_runtime::spawn_blocking(move || update(&app_handle, &tenant_id, tenant))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn update(app: &AppHandle, tenant_id: &str, tenant: TenantConfig) -> Result<(), String> {
+    let tenant = config::user::normalize_tenant(tenant)?;
+    {
+        let state = app.state::<ConfigState>();
+        let loaded = state.0.lock().map_err(|e| e.to_string())?;
+        check_unlocked(&loaded, tenant_id)?;
+    }
+
+    edit_user_config(app, |doc| config::user::set_tenant(doc, tenant_id, &tenant))?;
+
+    if let Some(window) = app.get_webview_window(&windows::label(tenant_id)) {
+        let _ = window.set_title(&window_title(tenant.name.as_deref().unwrap_or(tenant_id)));
+    }
+    info!("Updated tenant {} ({})", tenant_id, tenant.app_url);
+    Ok(())
+}
+
+/// Removes a tenant that was added in the app and closes its window.
+///
+/// The active and default tenants cannot be removed.
+#[tauri::command]
+pub async fn remove_tenant(tenant_id: String, window: WebviewWindow, app_handle: AppHandle) -> Result<(), String> {
+    authorize(&app_handle, &window, Some(&tenant_id)).await?;
+    tauri::async_runtime::spawn_blocking(move || remove(&app_handle, &tenant_id))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn remove(app: &AppHandle, tenant_id: &str) -> Result<(), String> {
+    let active = active_id(app);
+    {
+        let state = app.state::<ConfigState>();
+        let loaded = state.0.lock().map_err(|e| e.to_string())?;
+        check_removable(&loaded, active.as_deref(), tenant_id)?;
+    }
+
+    edit_user_config(app, |doc| {
+        config::user::remove_tenant(doc, tenant_id);
+        Ok(())
+    })?;
+
+    if let Some(window) = app.get_webview_window(&windows::label(tenant_id)) {
+        let _ = window.destroy();
+    }
+    info!("Removed tenant {}", tenant_id);
+    Ok(())
+}
+
+/// Looks up a tenant for editing, refusing locked ones.
+fn check_unlocked(loaded: &LoadedConfig, tenant_id: &str) -> Result<TenantEntry, String> {
+    let entry = entries(loaded)
+        .into_iter()
+        .find(|entry| entry.id == tenant_id)
+        .ok_or_else(|| format!("Tenant `{}` is not configured", tenant_id))?;
+
+    if entry.locked {
+        return Err(format!(
+            "Tenant `{}` is managed outside the app (system config, remote catalogue, \
+             environment or command line) and cannot be changed here",
+            tenant_id
+        ));
+    }
+    Ok(entry)
+}
+
+/// Refuses to remove locked tenants, those of the installed config, the active tenant and the default one.
+fn check_removable(loaded: &LoadedConfig, active_id: Option<&str>, tenant_id: &str) -> Result<(), String> {
+    let entry = check_unlocked(loaded, tenant_id)?;
+    if !entry.removable {
+        return Err(format!(
+            "Tenant `{}` is part of the installed config and cannot be removed",
+            tenant_id
+        ));
+    }
+    if active_id == Some(tenant_id) {
+        return Err(format!("Switch to another tenant before removing `{}`", tenant_id));
+    }
+    if loaded.config.default_tenant == tenant_id {
+        return Err(format!("`{}` is the default tenant and cannot be removed", tenant_id));
+    }
+    Ok(())
+}
+
+/// Applies `change` to the user config file and reloads it, undoing the edit
+/// when the resulting config is rejected. Blocks; keep it off the main thread.
+///
+/// Reloading rebuilds the tray and emits `config-changed`, like an edit by hand.
+/// The config watcher then sees the write and reloads once more; that finds
+/// nothing new, so no second `config-changed` is sent.
+fn edit_user_config<F>(app: &AppHandle, change: F) -> Result<(), String>
+where
+    F: FnOnce(&mut serde_json::Map<String, serde_json::Value>) -> Result<(), String>,
+{
+    let paths = config::layer_paths(app, &config::current_env());
+    let path = config::user::file(&paths).ok_or("No user config directory is available")?;
+    let backup = config::user::edit(path, change)?;
+
+    // Before startup has completed there is nothing to reload yet.
+    if app.try_state::<ConfigState>().is_none() {
+        return Ok(());
+    }
+    if let Err(e) = config::watch::reload(app) {
+        if let Err(restore) = backup.restore() {
+            error!("Failed to undo rejected config edit: {}", restore);
+        }
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+fn last_tenant(app: &AppHandle) -> Option<String> {
+    let store = app.store(STATE_STORE).ok()?;
+    store.get(LAST_TENANT_KEY)?.as_str().map(String::from)
+}
+
+fn remember(app: &AppHandle, tenant_id: &str) {
+    let result = app.store(STATE_STORE).and_then(|store| {
+        store.set(LAST_TENANT_KEY, tenant_id);
+        store.save()
+    });
+
+    if let Err(e) = result {
+        warn!("Failed to remember tenant {}: {}", tenant_id, e);
+    }
+}
+
+/// Switches from the tray and logs a failure, as there is no caller to return it to.
+///
+/// Menu events run on the main thread, where building the window deadlocks on
+/// Windows, so the switch runs on the async runtime.
+pub fn switch_from_menu(app: &AppHandle, tenant_id: &str) {
+    let app = app.clone();
+    let tenant_id = tenant_id.to_string();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = switch(&app, &tenant_id) {
+            error!("Failed to switch to tenant {}: {}", tenant_id, e);
+        }
+        // Check items toggle themselves when clicked, so redraw them from state.
+        crate::refresh_tray(&app);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A config with a bundled tenant, one overridden by the user, one added
+    /// by the user, one from the system config and one touched by `--app-url`.
+    fn loaded() -> LoadedConfig {
+        let config = serde_json::from_value(json!({
+            "env": "dev",
+            "defaultTenant": "acme",
+            "tenants": {
+                "acme": { "appUrl": "https://acme.example.com" },
+                "globex": { "appUrl": "https://globex.example.com", "name": "Globex" },
+                "demo": { "appUrl": "https://demo.example.com" },
+                "initech": { "appUrl": "https://initech.example.com" },
+                "umbrella": { "appUrl": "http://localhost:3000" }
+            }
+        }))
+        .unwrap();
+        let origins = [
+            ("tenants.acme.appUrl", ConfigLayer::Bundled),
+            ("tenants.globex.appUrl", ConfigLayer::Bundled),
+            ("tenants.globex.name", ConfigLayer::User),
+            ("tenants.demo.appUrl", ConfigLayer::User),
+            ("tenants.initech.appUrl", ConfigLayer::System),
+            ("tenants.umbrella.appUrl", ConfigLayer::Cli),
+        ];
+        LoadedConfig {
+            config,
+            files: Vec::new(),
+            origins: origins.into_iter().map(|(path, layer)| (path.to_string(), layer)).collect(),
+            remote: None,
+        }
+    }
+
+    #[test]
+    fn lists_where_each_tenant_comes_from() {
+        let entries = entries(&loaded());
+        let summary: Vec<(&str, ConfigLayer, bool, bool)> = entries
+            .iter()
+            .map(|entry| (entry.id.as_str(), entry.source, entry.locked, entry.removable))
+            .collect();
+
+        assert_eq!(
+            summary,
+            [
+                ("acme", ConfigLayer::Bundled, false, false),
+                ("demo", ConfigLayer::User, false, true),
+                ("globex", ConfigLayer::User, false, false),
+                ("initech", ConfigLayer::System, true, false),
+                ("umbrella", ConfigLayer::Cli, true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn managed_tenants_cannot_be_changed() {
+        let loaded = loaded();
+        assert_eq!(check_unlocked(&loaded, "globex").unwrap().id, "globex");
+        assert!(check_unlocked(&loaded, "acme").is_ok());
+        assert!(check_unlocked(&loaded, "initech").unwrap_err().contains("managed outside the app"));
+        assert!(check_unlocked(&loaded, "umbrella").is_err());
+        assert!(check_unlocked(&loaded, "missing").unwrap_err().contains("not configured"));
+    }
+
+    #[test]
+    fn only_inactive_user_tenants_can_be_removed() {
+        let mut loaded = loaded();
+        assert!(check_removable(&loaded, Some("acme"), "demo").is_ok());
+        assert!(check_removable(&loaded, None, "globex").unwrap_err().contains("installed config"));
+        assert!(check_removable(&loaded, None, "initech").unwrap_err().contains("managed outside the app"));
+        assert!(check_removable(&loaded, Some("demo"), "demo").unwrap_err().contains("Switch to another tenant"));
+
+        loaded.config.default_tenant = "demo".to_string();
+        assert!(check_removable(&loaded, Some("acme"), "demo").unwrap_err().contains("default tenant"));
+    }
+}
@@ -0,0 +1,340 @@
+use log::{error, info, warn};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::ipc::CapabilityBuilder;
+use tauri::webview::NewWindowResponse;
+use tauri::{AppHandle, Manager, Url, WebviewWindow, WebviewWindowBuilder, WindowEvent};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::config::{ConfigState, TenantConfig};
+use crate::{connectivity, pages, tenant};
+
+/// Label of the window in `tauri.conf.json` that tenant windows are created from.
+const TEMPLATE_LABEL: &str = "main";
+
+/// Prefix of tenant window labels; the capability in `capabilities/default.json` matches it.
+pub const LABEL_PREFIX: &str = "tenant-";
+
+/// Prefix of the tray menu item ids that open a tenant in a new window.
+pub const OPEN_MENU_ID_PREFIX: &str = "open-tenant:";
+
+/// Prefix of the tray menu item ids that bring an open tenant window to the front.
+pub const WINDOW_MENU_ID_PREFIX: &str = "window:";
+
+/// Label of the tenant window that was focused last, which tray actions apply to.
+#[derive(Default)]
+pub struct LastFocused(pub Mutex<Option<String>>);
+
+/// Encodes a tenant id into something safe for window labels and directory names.
+///
+/// Lowercase ASCII letters, digits and `-` are kept; every other byte becomes `_xx`,
+/// so distinct ids never share a key, even on case-insensitive file systems.
+pub fn storage_key(tenant_id: &str) -> String {
+    let mut key = String::with_capacity(tenant_id.len());
+    for byte in tenant_id.bytes() {
+        match byte {
+            b'a'..=b'z' | b'0'..=b'9' | b'-' => key.push(byte as char),
+            _ => key.push_str(&format!("_{:02x}", byte)),
+        }
+    }
+    key
+}
+
+/// Label of the window showing `tenant_id`.
+pub fn label(tenant_id: &str) -> String {
+    format!("{}{}", LABEL_PREFIX, storage_key(tenant_id))
+}
+
+/// Directory holding a tenant's cookies, local storage and cache.
+///
+/// Not used on macOS, where WebKit keeps data stores itself; see `data_store_id`.
+pub fn data_dir(app: &AppHandle, tenant_id: &str) -> tauri::Result<PathBuf> {
+    Ok(app
+        .path()
+        .app_local_data_dir()?
+        .join("webview")
+        .join(storage_key(tenant_id)))
+}
+
+/// Identifier of a tenant's WebKit data store (macOS 14 and later).
+#[cfg(target_os = "macos")]
+fn data_store_id(tenant_id: &str) -> [u8; 16] {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::digest(format!("smartops-tenant:{}", storage_key(tenant_id)));
+    let mut id = [0; 16];
+    id.copy_from_slice(&digest[..16]);
+    id
+}
+
+/// The window of the active tenant.
+pub fn main(app: &AppHandle) -> Option<WebviewWindow> {
+    app.get_webview_window(&label(&tenant::active_id(app)?))
+}
+
+/// The tenant shown in the window labelled `window_label`.
+pub fn tenant_id(app: &AppHandle, window_label: &str) -> Option<String> {
+    let state = app.try_state::<ConfigState>()?;
+    let loaded = state.0.lock().ok()?;
+    loaded.config.tenants.keys().find(|id| label(id) == window_label).cloned()
+}
+
+/// Open tenant windows, sorted by title.
+pub fn tenant_windows(app: &AppHandle) -> Vec<WebviewWindow> {
+    let mut windows: Vec<WebviewWindow> = app
+        .webview_windows()
+        .into_values()
+        .filter(|w| w.label().starts_with(LABEL_PREFIX))
+        .collect();
+    windows.sort_by_cached_key(|w| w.title().unwrap_or_default());
+    windows
+}
+
+/// The window tray actions apply to: the focused tenant window, else the one
+/// focused last, else the active tenant's window.
+pub fn target(app: &AppHandle) -> Option<WebviewWindow> {
+    let windows = tenant_windows(app);
+    if let Some(focused) = windows.iter().find(|w| w.is_focused().unwrap_or(false)) {
+        return Some(focused.clone());
+    }
+
+    let last = app.try_state::<LastFocused>().and_then(|state| state.0.lock().ok()?.clone());
+    last.and_then(|label| app.get_webview_window(&label))
+        .or_else(|| main(app))
+}
+
+/// Returns true if a tenant window may navigate to `url`.
+///
+/// Web pages are confined to the tenant's origin; the shell's own pages and
+/// schemes (`tauri:`, `desktop:`, `about:`, `blob:`) are always allowed.
+/// Everything else is refused: `mailto:`/`tel:` links belong to the system,
+/// and schemes such as `data:`, `file:`, `javascript:` or `smartops:` never
+/// load in a tenant window, so no link can fill it with arbitrary content
+/// under the tenant's title.
+pub fn navigation_allowed(tenant_url: &Url, url: &Url) -> bool {
+    match url.scheme() {
+        "http" | "https" => url.origin() == tenant_url.origin() || pages::is_page(url),
+        scheme if scheme == pages::SCHEME => true,
+        "tauri" | "about" | "blob" => true,
+        _ => false,
+    }
+}
+
+/// Returns the tenant's window, creating it with its own storage if it is not open.
+///
+/// New windows start hidden on the offline page and load the tenant's `appUrl`
+/// once it is known to be reachable, see `connectivity::watch`. Navigation
+/// outside the tenant origin and `window.open` go to the system browser, like
+/// `will-navigate` and `setWindowOpenHandler` did in the Electron shell.
+pub fn open(app: &AppHandle, tenant_id: &str, tenant: &TenantConfig) -> tauri::Result<WebviewWindow> {
+    let label = label(tenant_id);
+    if let Some(window) = app.get_webview_window(&label) {
+        return Ok(window);
+    }
+
+    let mut config = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|w| w.label == TEMPLATE_LABEL)
+        .cloned()
+        .unwrap_or_default();
+    config.label = label;
+    config.title = tenant::window_title(tenant.name.as_deref().unwrap_or(tenant_id));
+    config.visible = false;
+    config.url = pages::url(connectivity::OFFLINE_PAGE);
+
+    let tenant_url = Url::parse(&tenant.app_url).map_err(tauri::Error::InvalidUrl)?;
+    allow_ipc(app, &config.label, &tenant_url);
+
+    let guard_app = app.clone();
+    let guard_url = tenant_url.clone();
+    let new_window_app = app.clone();
+    let builder = WebviewWindowBuilder::from_config(app, &config)?
+        .on_navigation(move |url| {
+            let allowed = navigation_allowed(&guard_url, url);
+            if !allowed {
+                open_externally(&guard_app, url);
+            }
+            allowed
+        })
+        .on_new_window(move |url, _features| {
+            open_externally(&new_window_app, &url);
+            NewWindowResponse::Deny
+        });
+    #[cfg(not(target_os = "macos"))]
+    let builder = builder.data_directory(data_dir(app, tenant_id)?);
+    #[cfg(target_os = "macos")]
+    let builder = builder.data_store_identifier(data_store_id(tenant_id));
+
+    let window = builder.build()?;
+    info!("Opened window {} for tenant {}", window.label(), tenant_id);
+    connectivity::watch(app, &window, tenant_id, tenant_url);
+
+    let window_clone = window.clone();
+    window.on_window_event(move |event| on_window_event(&window_clone, event));
+    crate::refresh_tray(app);
+
+    Ok(window)
+}
+
+/// Lets the tenant's web app use the shell's commands and events from its remote origin.
+///
+/// Only core and notification permissions are granted; file system, shell and
+/// clipboard access stay with the shell's own pages.
+fn allow_ipc(app: &AppHandle, label: &str, tenant_url: &Url) {
+    let capability = CapabilityBuilder::new(format!("remote-{}", label))
+        .remote(format!("{}/*", tenant_url.origin().ascii_serialization()))
+        .window(label)
+        .permission("core:default")
+        .permission("notification:default");
+
+    if let Err(e) = app.add_capability(capability) {
+        warn!("Failed to allow IPC for {}: {}", tenant_url, e);
+    }
+}
+
+/// Hands a link the tenant window may not open to the system browser.
+fn open_externally(app: &AppHandle, url: &Url) {
+    if !matches!(url.scheme(), "http" | "https" | "mailto" | "tel") {
+        warn!("Blocked navigation to {}", url);
+        return;
+    }
+
+    info!("Opening {} in the system browser", url);
+    if let Err(e) = app.opener().open_url(url.as_str(), None::<&str>) {
+        error!("Failed to open {}: {}", url, e);
+    }
+}
+
+fn on_window_event(window: &WebviewWindow, event: &WindowEvent) {
+    let app = window.app_handle();
+    match event {
+        WindowEvent::Focused(true) => {
+            if let Ok(mut last) = app.state::<LastFocused>().0.lock() {
+                *last = Some(window.label().to_string());
+            }
+        }
+        // Closing the active tenant's window only hides it on macOS, where apps
+        // keep running without windows. Other tenant windows close for real.
+        #[cfg(target_os = "macos")]
+        WindowEvent::CloseRequested { api, .. } if main(app).is_some_and(|main| main.label() == window.label()) => {
+            api.prevent_close();
+            let _ = window.hide();
+        }
+        WindowEvent::Destroyed => {
+            connectivity::forget(app, window.label());
+            crate::refresh_tray(app);
+        }
+        _ => {}
+    }
+}
+
+/// Opens a tenant in its own window next to the ones already open, or focuses it.
+pub fn open_in_new_window(app: &AppHandle, tenant_id: &str) -> Result<(), String> {
+    let tenant = {
+        let state = app.state::<ConfigState>();
+        let loaded = state.0.lock().map_err(|e| e.to_string())?;
+        loaded.config.tenant(tenant_id)?.clone()
+    };
+    let window = open(app, tenant_id, &tenant).map_err(|e| e.to_string())?;
+    let _ = window.show();
+    let _ = window.set_focus();
+    Ok(())
+}
+
+/// Opens a tenant from the tray and logs a failure, as there is no caller to return it to.
+///
+/// Menu events run on the main thread, where building the window deadlocks on
+/// Windows, so the window is opened on the async runtime.
+pub fn open_from_menu(app: &AppHandle, tenant_id: &str) {
+    let app = app.clone();
+    let tenant_id = tenant_id.to_string();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = open_in_new_window(&app, &tenant_id) {
+            error!("Failed to open window for tenant {}: {}", tenant_id, e);
+        }
+    });
+}
+
+/// Async so the window is not built on the main thread, which deadlocks on Windows.
+#[tauri::command]
+pub async fn open_tenant_window(tenant_id: String, app_handle: AppHandle) -> Result<(), String> {
+    open_in_new_window(&app_handle, &tenant_id)
+}
+
+
+/// Deletes a tenant's cookies, local storage and cache.
+///
+/// An open tenant window is cleared in place; otherwise its data directory
+/// (or WebKit data store) is removed.
+pub async fn wipe_data(app: &AppHandle, tenant_id: &str) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window(&label(tenant_id)) {
+        window.clear_all_browsing_data().map_err(|e| e.to_string())?;
+        info!("Cleared browsing data of tenant {}", tenant_id);
+        return Ok(());
+    }
+
+    #[cfg(target_os = "macos")]
+    app.remove_data_store(data_store_id(tenant_id))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let dir = data_dir(app, tenant_id).map_err(|e| e.to_string())?;
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| e.to_string())?;
+    }
+
+    info!("Wiped webview data of tenant {}", tenant_id);
+    Ok(())
+}
+
+/// Deletes a tenant's cookies, local storage and cache, reloading its window if it is open.
+#[tauri::command]
+pub async fn wipe_tenant_data(tenant_id: String, app_handle: AppHandle) -> Result<(), String> {
+    wipe_data(&app_handle, &tenant_id).await?;
+    match app_handle.get_webview_window(&label(&tenant_id)) {
+        Some(window) => window.reload().map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_keys_are_safe_and_distinct() {
+        assert_eq!(storage_key("acme-prod"), "acme-prod");
+        assert_eq!(storage_key("../etc"), "_2e_2e_2fetc");
+
+        let ids = ["Acme", "acme", "a_b", "a.b", "a_2eb", "a/b"];
+        let mut keys: Vec<String> = ids.iter().map(|id| storage_key(id)).collect();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), ids.len());
+    }
+
+    #[test]
+    fn navigation_is_confined_to_the_tenant_origin() {
+        let tenant = Url::parse("https://acme.example.com/app/").unwrap();
+        let allowed = |url: &str| navigation_allowed(&tenant, &Url::parse(url).unwrap());
+
+        assert!(allowed("https://acme.example.com/other/page"));
+        assert!(allowed("tauri://localhost/index.html"));
+        assert!(!allowed("http://acme.example.com/"));
+        assert!(!allowed("https://acme.example.com:8443/"));
+        assert!(!allowed("https://evil.example.com/"));
+        assert!(!allowed("mailto:support@example.com"));
+        assert!(allowed("desktop://localhost/offline.html"));
+        assert!(allowed("about:blank"));
+        assert!(allowed("blob:https://acme.example.com/0b8e6c1c"));
+        assert!(!allowed("data:text/html,<h1>Sign in again</h1>"));
+        assert!(!allowed("file:///etc/passwd"));
+        assert!(!allowed("javascript:alert(1)"));
+        assert!(!allowed("smartops://acme/open"));
+        assert!(!allowed("vscode://file/etc/passwd"));
+    }
+}
@@ -1,4 +1,5 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { open } from '@tauri-apps/plugin-dialog';
 import { readTextFile, writeTextFile } from '@tauri-apps/plugin-fs';
 import { readText, writeText } from '@tauri-apps/plugin-clipboard-manager';
@@ -12,9 +13,182 @@ export interface DesktopConfig {
   tenantName: string;
 }
 
+export type DesktopTenant = Omit<DesktopConfig, 'env'>;
+
+export type DesktopTokenTenantOutcome =
+  | { outcome: 'noClaim' }
+  | { outcome: 'matched' | 'switched' | 'declined'; tenantId: string };
+
+export interface DesktopConnectivityStatus {
+  tenantId: string;
+  online: boolean;
+  failure?: { stage: 'dns' | 'tcp' | 'tls' | 'http'; message: string };
+  checkedAt: number;
+  lastSuccess: number | null;
+}
+
+export interface DesktopTenantSettings {
+  appUrl: string;
+  name?: string | null;
+  healthUrl?: string | null;
+}
+
+export interface DesktopTenantEntry extends DesktopTenantSettings {
+  id: string;
+  source: DesktopConfigLayer;
+  locked: boolean;
+  removable: boolean;
+}
+
+export interface DesktopSession {
+  tenantId: string;
+  accessToken: string;
+  tokenType: string;
+  idToken: string | null;
+  expiresAt: number;
+}
+
+export interface DesktopIdentity {
+  tenantId: string;
+  subject: string;
+  username: string | null;
+  name: string | null;
+  email: string | null;
+  tenant: string | null;
+  roles: string[];
+  expiresAt: number;
+}
+
+export type DesktopLogoutScope = 'session' | 'full';
+
+export interface DesktopSessionExpiring {
+  tenantId: string;
+  expiresAt: number;
+}
+
+export interface DesktopSessionExpired {
+  tenantId: string;
+  reason: string;
+}
+
+export interface DesktopSecureKey {
+  key: string;
+  label: string | null;
+  expiresAt: number | null;
+}
+
+export interface DesktopTenantKeys {
+  tenantId: string;
+  keys: DesktopSecureKey[];
+}
+
+export interface DesktopSecureStoreOptions {
+  ttlSecs?: number;
+  label?: string;
+}
+
+export interface DesktopSecureStoreMigration {
+  source: string;
+  tenantId: string;
+  migrated: string[];
+  failed: Array<{ key: string; reason: string }>;
+  archivedTo: string | null;
+}
+
+export interface DesktopSecureStoreStatus {
+  locked: boolean;
+  autoLockMinutes: number | null;
+  hasPassphrase: boolean;
+}
+
+export interface DesktopConfigChange {
+  path: string;
+  old?: unknown;
+  new?: unknown;
+}
+
+export type DesktopConfigLayer =
+  | 'bundled'
+  | 'remote'
+  | 'user'
+  | 'system'
+  | 'env'
+  | 'cli';
+
+export interface DesktopConfigSources {
+  files: Array<{ layer: DesktopConfigLayer; path: string; present: boolean; migratedFrom?: number }>;
+  origins: Record<string, DesktopConfigLayer>;
+  remote: {
+    url: string;
+    source: 'network' | 'cache';
+    fetchedAt: number;
+    error?: string;
+  } | null;
+}
+
 const desktopApi = {
   getConfig: (): Promise<DesktopConfig> => invoke<DesktopConfig>('get_config'),
 
+  getConfigSources: (): Promise<DesktopConfigSources> =>
+    invoke<DesktopConfigSources>('get_config_sources'),
+
+  reloadConfig: (): Promise<DesktopConfigChange[]> =>
+    invoke<DesktopConfigChange[]>('reload_config'),
+
+  onConfigChanged: (
+    handler: (changes: DesktopConfigChange[]) => void,
+  ): Promise<UnlistenFn> =>
+    listen<{ changes: DesktopConfigChange[] }>('config-changed', event =>
+      handler(event.payload.changes),
+    ),
+
+  switchTenant: (tenantId: string): Promise<void> =>
+    invoke<void>('switch_tenant', { tenantId }),
+
+  selectTenantFromToken: (): Promise<DesktopTokenTenantOutcome> =>
+    invoke<DesktopTokenTenantOutcome>('select_tenant_from_token'),
+
+  listTenants: (): Promise<DesktopTenantEntry[]> => invoke<DesktopTenantEntry[]>('list_tenants'),
+
+  addTenant: (tenantId: string, tenant: DesktopTenantSettings): Promise<void> =>
+    invoke<void>('add_tenant', { tenantId, tenant }),
+
+  updateTenant: (tenantId: string, tenant: DesktopTenantSettings): Promise<void> =>
+    invoke<void>('update_tenant', { tenantId, tenant }),
+
+  removeTenant: (tenantId: string): Promise<void> =>
+    invoke<void>('remove_tenant', { tenantId }),
+
+  openTenantWindow: (tenantId: string): Promise<void> =>
+    invoke<void>('open_tenant_window', { tenantId }),
+
+  retryConnection: (): Promise<void> => invoke<void>('retry_connection'),
+
+  onConnectivityChanged: (handler: (status: DesktopConnectivityStatus) => void): Promise<UnlistenFn> =>
+    listen<DesktopConnectivityStatus>('connectivity-changed', event => handler(event.payload)),
+
+  wipeTenantData: (tenantId: string): Promise<void> =>
+    invoke<void>('wipe_tenant_data', { tenantId }),
+
+  onTenantChanged: (handler: (tenant: DesktopTenant) => void): Promise<UnlistenFn> =>
+    listen<DesktopTenant>('tenant-changed', event => handler(event.payload)),
+
+  auth: {
+    login: (): Promise<DesktopSession> => invoke<DesktopSession>('login'),
+    getSession: (): Promise<DesktopSession | null> => invoke<DesktopSession | null>('get_session'),
+    getIdentity: (): Promise<DesktopIdentity> => invoke<DesktopIdentity>('get_identity'),
+    logout: (tenantId: string, scope?: DesktopLogoutScope): Promise<void> =>
+      invoke<void>('logout', { tenantId, scope }),
+    onSessionChanged: (handler: (session: DesktopSession) => void): Promise<UnlistenFn> =>
+      listen<DesktopSession>('session-changed', event => handler(event.payload)),
+    onTokenRefreshed: (handler: (session: DesktopSession) => void): Promise<UnlistenFn> =>
+      listen<DesktopSession>('auth-token-refreshed', event => handler(event.payload)),
+    onSessionExpiring: (handler: (notice: DesktopSessionExpiring) => void): Promise<UnlistenFn> =>
+      listen<DesktopSessionExpiring>('auth-session-expiring', event => handler(event.payload)),
+    onSessionExpired: (handler: (notice: DesktopSessionExpired) => void): Promise<UnlistenFn> =>
+      listen<DesktopSessionExpired>('auth-session-expired', event => handler(event.payload)),
+  },
+
   notify: (title: string, body: string): Promise<void> => {
     return new Promise(async (resolve) => {
       let permissionGranted = await isPermissionGranted();
@@ -82,10 +256,26 @@ const desktopApi = {
   secureStore: {
     get: (key: string): Promise<string | null> =>
       invoke<string | null>('get_secure_store', { key }),
-    set: (key: string, value: string): Promise<void> =>
-      invoke('set_secure_store', { key, value }),
+    set: (key: string, value: string, options?: DesktopSecureStoreOptions): Promise<void> =>
+      invoke('set_secure_store', { key, value, options }),
     delete: (key: string): Promise<void> =>
       invoke('delete_secure_store', { key }),
+    keys: (): Promise<DesktopSecureKey[]> => invoke<DesktopSecureKey[]>('list_secure_keys'),
+    clear: (): Promise<void> => invoke('clear_secure_store'),
+    allKeys: (): Promise<DesktopTenantKeys[]> =>
+      invoke<DesktopTenantKeys[]>('list_all_secure_keys'),
+    status: (): Promise<DesktopSecureStoreStatus> =>
+      invoke<DesktopSecureStoreStatus>('get_secure_store_status'),
+    migration: (): Promise<DesktopSecureStoreMigration | null> =>
+      invoke<DesktopSecureStoreMigration | null>('get_secure_store_migration'),
+    unlock: (passphrase?: string): Promise<void> =>
+      invoke('unlock_secure_store', { passphrase }),
+    setPassphrase: (passphrase: string, currentPassphrase?: string): Promise<void> =>
+      invoke('set_secure_store_passphrase', { passphrase, currentPassphrase }),
+    onLocked: (handler: () => void): Promise<UnlistenFn> =>
+      listen('secure-store-locked', () => handler()),
+    onUnlocked: (handler: () => void): Promise<UnlistenFn> =>
+      listen('secure-store-unlocked', () => handler()),
   },
 
   autoLaunch: {